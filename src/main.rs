@@ -1,8 +1,24 @@
 mod nes;
-use nes::{cart::Cart, open_ines_file, Nes};
+use std::{
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use nes::{
+    cart::{database, Cart},
+    open_rom_file,
+    player::Player,
+    ppu::{Frame, PixelFormat},
+    read_rom_info, Nes,
+};
 use sdl2::{event::Event, keyboard::Keycode, pixels::PixelFormatEnum};
 
-use crate::nes::ppu::show_tile_bank;
+use crate::nes::ppu::{show_tile_bank, Palette};
+
+const USAGE: &str = "usage: nes_emulator <rom>
+       nes_emulator tiles <rom>
+       nes_emulator info [--json] <rom>...";
 
 fn show_tiles(cart: Cart) {
     let sdl_context = sdl2::init().unwrap();
@@ -22,29 +38,280 @@ fn show_tiles(cart: Cart) {
         .create_texture_target(PixelFormatEnum::RGB24, 256, 240)
         .unwrap();
 
-    let right_bank = show_tile_bank(&cart.chr_rom, 1);
+    let palette = Palette::new(Default::default());
+    let right_bank = show_tile_bank(&cart.chr_rom, 1, &palette);
 
-    texture.update(None, &right_bank.data, 256 * 3).unwrap();
+    texture
+        .update(None, &right_bank.data, right_bank.pitch())
+        .unwrap();
     canvas.copy(&texture, None, None).unwrap();
     canvas.present();
 
-    loop {
+    // Leave the loop rather than exiting so the cart is dropped and its
+    // battery save flushed.
+    'running: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+                _ => { /* do nothing */ }
+            }
+        }
+    }
+}
+
+/// The settings of the console's extra controls, kept between key presses.
+struct Controls {
+    disk_side: usize,
+    dip_switches: u8,
+}
+
+/// Runs a game in a window until it is closed.
+fn run_game(cart: Cart) {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let window = video_subsystem
+        .window("NES", (256.0 * 3.0) as u32, (240.0 * 3.0) as u32)
+        .position_centered()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().build().unwrap();
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    canvas.set_scale(3.0, 3.0).unwrap();
+
+    let creator = canvas.texture_creator();
+    let mut texture = creator
+        .create_texture_target(PixelFormatEnum::RGB24, 256, 240)
+        .unwrap();
+
+    let mut nes = Nes::new(cart);
+    let palette = Palette::new(nes.bus.ppu.model);
+    let frame_time = Duration::from_secs_f64(1.0 / nes.timing().frame_rate());
+    let mut controls = Controls {
+        disk_side: 0,
+        dip_switches: 0,
+    };
+
+    'running: loop {
+        let frame_start = Instant::now();
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
-                } => std::process::exit(0),
+                } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => press_key(&mut nes, &mut controls, keycode),
+                Event::KeyUp {
+                    keycode: Some(Keycode::S),
+                    ..
+                } => nes.set_service_button(false),
                 _ => { /* do nothing */ }
             }
         }
+
+        let frame = next_frame(&mut nes, &palette);
+        texture.update(None, &frame.data, frame.pitch()).unwrap();
+        canvas.copy(&texture, None, None).unwrap();
+        canvas.present();
+        if let Some(rest) = frame_time.checked_sub(frame_start.elapsed()) {
+            thread::sleep(rest);
+        }
     }
 }
 
+/// D puts the next disk side in the drive and E ejects it. On a Vs. System
+/// C and V insert coins, S holds the service button and 1 to 8 flip the DIP
+/// switches.
+fn press_key(nes: &mut Nes, controls: &mut Controls, keycode: Keycode) {
+    let num_sides = nes.bus.cart.num_disk_sides();
+    let is_vs_system = nes.bus.io.vs_system.is_some();
+    match keycode {
+        Keycode::D if num_sides > 0 => {
+            controls.disk_side = (controls.disk_side + 1) % num_sides;
+            nes.bus.cart.insert_disk(controls.disk_side);
+            println!("disk side {} of {}", controls.disk_side + 1, num_sides);
+        }
+        Keycode::E if num_sides > 0 => {
+            nes.bus.cart.eject_disk();
+            println!("disk ejected");
+        }
+        Keycode::C if is_vs_system => nes.insert_coin(0),
+        Keycode::V if is_vs_system => nes.insert_coin(1),
+        Keycode::S if is_vs_system => nes.set_service_button(true),
+        _ if is_vs_system => {
+            if let Some(switch) = dip_switch(keycode) {
+                controls.dip_switches ^= 1 << switch;
+                nes.set_dip_switches(controls.dip_switches);
+                // Switch 1 first, the way they are laid out on the board.
+                println!("DIP switches {:08b}", controls.dip_switches.reverse_bits());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The DIP switch, counting from 0, that a number key flips.
+fn dip_switch(keycode: Keycode) -> Option<u8> {
+    return match keycode {
+        Keycode::Num1 => Some(0),
+        Keycode::Num2 => Some(1),
+        Keycode::Num3 => Some(2),
+        Keycode::Num4 => Some(3),
+        Keycode::Num5 => Some(4),
+        Keycode::Num6 => Some(5),
+        Keycode::Num7 => Some(6),
+        Keycode::Num8 => Some(7),
+        _ => None,
+    };
+}
+
+/// Plays an NSF until the window is closed. Left and Right step through the
+/// tracks, and the window title says which one is playing.
+fn play_music(cart: Cart) {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let window = video_subsystem
+        .window("NSF player", 512, 64)
+        .position_centered()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().build().unwrap();
+    let mut event_pump = sdl_context.event_pump().unwrap();
+
+    let mut player = Player::new(cart);
+    let frame_rate = player.nes.timing().frame_rate();
+    let cycles_per_frame = (player.nes.timing().cpu_frequency / frame_rate) as i64;
+    let frame_time = Duration::from_secs_f64(1.0 / frame_rate);
+    let mut title = String::new();
+
+    'running: loop {
+        let frame_start = Instant::now();
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Left),
+                    ..
+                } => player.previous_track(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Right),
+                    ..
+                } => player.next_track(),
+                _ => { /* do nothing */ }
+            }
+        }
+
+        for _ in 0..cycles_per_frame {
+            player.tick();
+        }
+        let track_title = track_title(&player);
+        if track_title != title {
+            title = track_title;
+            canvas.window_mut().set_title(&title).unwrap();
+        }
+        canvas.clear();
+        canvas.present();
+        if let Some(rest) = frame_time.checked_sub(frame_start.elapsed()) {
+            thread::sleep(rest);
+        }
+    }
+}
+
+fn track_title(player: &Player) -> String {
+    let number = format!("Track {}/{}", player.track + 1, player.num_tracks);
+    return match player.track_title() {
+        Some(title) => format!("{}: {}", number, title),
+        None => number,
+    };
+}
+
+/// Runs the console until the PPU finishes a frame.
+fn next_frame(nes: &mut Nes, palette: &Palette) -> Frame {
+    loop {
+        nes.tick();
+        if let Some(picture) = nes.bus.ppu.poll_frame() {
+            return palette.convert(picture, PixelFormat::Rgb24);
+        }
+    }
+}
+
+fn open_cart(filename: &str) -> Cart {
+    let cart = match open_rom_file(filename, &Default::default()) {
+        Ok(cart) => cart,
+        Err(error) => {
+            eprintln!("{}: {}", filename, error);
+            std::process::exit(1);
+        }
+    };
+    for correction in &cart.corrections {
+        println!("header corrected: {}", correction);
+    }
+    return cart;
+}
+
+/// `info [--json] <rom>...` prints what each ROM's header and hashes say
+/// about it. Returns the exit code, which is nonzero if any file failed.
+fn show_info(args: &[String]) -> i32 {
+    let is_json = args.iter().any(|arg| arg == "--json");
+    let filenames: Vec<&String> = args.iter().filter(|arg| *arg != "--json").collect();
+    if filenames.is_empty() {
+        eprintln!("usage: info [--json] <rom>...");
+        return 2;
+    }
+    let mut result = 0;
+    for filename in filenames {
+        match read_rom_info(filename, &Default::default()) {
+            Ok(info) if is_json => println!("{}", info.to_json(filename)),
+            Ok(info) => println!("{}\n{}", filename, info),
+            Err(error) => {
+                eprintln!("{}: {}", filename, error);
+                result = 1;
+            }
+        }
+    }
+    return result;
+}
+
+/// The NES 2.0 XML database, used for header correction when present.
+/// Without it only the few games built into `database` are corrected.
+const DATABASE_FILE: &str = "nes20db.xml";
+
 fn main() {
-    let cart = open_ines_file("super_mario.nes");
-    // println!("{:?}", cart.header);
-    // println!("{:X}", cart.header.control.get_mapper());
-    show_tiles(cart);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let database_path = Path::new(DATABASE_FILE);
+    if database_path.is_file() {
+        if let Err(error) = database::load(database_path) {
+            eprintln!("{}: {}", DATABASE_FILE, error);
+        }
+    }
+    match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        ["info", ..] => std::process::exit(show_info(&args[1..])),
+        ["tiles", filename] => show_tiles(open_cart(filename)),
+        [filename] => {
+            let cart = open_cart(filename);
+            if cart.nsf.is_some() {
+                play_music(cart);
+            } else {
+                run_game(cart);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
 }
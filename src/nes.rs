@@ -1,10 +1,15 @@
-use std::{fs::File, io::Read, thread::sleep};
+use std::{
+    fs,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use bus::Bus;
-use cart::Cart;
+use cart::{archive, header::Region, info::RomInfo, patch, Cart, LoadError};
 use cpu::{decode, execute, fetch};
 use inturrupts::reset;
-use std::{thread, time};
+use timing::Timing;
 
 // mod apu;
 mod bus;
@@ -14,30 +19,43 @@ pub mod memory;
 pub mod ppu;
 // mod test;
 mod inturrupts;
+mod io;
+pub mod player;
+pub mod timing;
+mod vs_system;
 
-const CYCLES_PER_SECOND: f64 = 1790000.0;
-const WAIT_TIME: f64 = 1000.0 / CYCLES_PER_SECOND;
+const FDS_BIOS_FILE: &str = "disksys.rom";
+const PATCH_EXTENSIONS: [&str; 2] = ["ips", "bps"];
 
 pub struct Nes {
     pub bus: Bus,
-    wait_time: time::Duration,
 }
 
 impl Default for Nes {
     fn default() -> Self {
         Self {
             bus: Default::default(),
-            wait_time: time::Duration::from_secs_f64(WAIT_TIME),
         }
     }
 }
 
 impl Nes {
+    /// Builds a console for the cart's own region.
     pub fn new(cart: Cart) -> Self {
-        Self {
-            bus: Bus::new(cart),
-            wait_time: time::Duration::from_secs_f64(WAIT_TIME),
-        }
+        let region = cart.region();
+        return Self::with_region(cart, region);
+    }
+
+    /// Builds a console for a chosen region, whatever the cart says.
+    pub fn with_region(cart: Cart, region: Region) -> Self {
+        let timing = Timing::new(region);
+        return Self {
+            bus: Bus::with_timing(cart, timing),
+        };
+    }
+
+    pub fn timing(&self) -> &Timing {
+        return &self.bus.timing;
     }
 
     pub fn tick(&mut self) {
@@ -74,19 +92,115 @@ impl Nes {
         reset(&mut self.bus);
     }
 
+    /// Drops a coin into one of a Vs. cabinet's two slots.
+    pub fn insert_coin(&mut self, slot: usize) {
+        if let Some(vs_system) = &mut self.bus.io.vs_system {
+            vs_system.insert_coin(slot);
+        }
+    }
+
+    pub fn set_service_button(&mut self, is_pressed: bool) {
+        if let Some(vs_system) = &mut self.bus.io.vs_system {
+            vs_system.is_service_pressed = is_pressed;
+        }
+    }
+
+    /// Sets a Vs. cabinet's eight DIP switches, switch 1 in bit 0.
+    pub fn set_dip_switches(&mut self, dip_switches: u8) {
+        if let Some(vs_system) = &mut self.bus.io.vs_system {
+            vs_system.dip_switches = dip_switches;
+        }
+    }
+
     pub fn run(&mut self) {
         // println!("{:X}", self.bus.cpu.program_counter);
         loop {
-            // sleep(self.wait_time);
             self.tick_debug();
         }
     }
 }
 
-pub fn open_ines_file(filename: &str) -> Cart {
-    let mut file = File::open(filename).expect("file not found");
+pub fn open_ines_file(filename: &str) -> Result<Cart, LoadError> {
+    let mut file = File::open(filename)?;
     let mut raw_data = Vec::new();
-    file.read_to_end(&mut raw_data);
-    let result = Cart::new(&raw_data);
-    return result;
+    file.read_to_end(&mut raw_data)?;
+    let mut result = Cart::new(&raw_data)?;
+    result.attach_save_file(filename);
+    return Ok(result);
+}
+
+/// Optional extras for `open_rom_file`.
+pub struct LoadOptions {
+    /// The entry to load from a ZIP archive instead of the first ROM in it.
+    pub entry: Option<String>,
+    /// Patch files applied in order after any found next to the ROM.
+    pub patches: Vec<String>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            entry: None,
+            patches: vec![],
+        }
+    }
+}
+
+/// Opens any supported ROM format, picking the loader from the file contents.
+pub fn open_rom_file(filename: &str, options: &LoadOptions) -> Result<Cart, LoadError> {
+    let (rom_path, raw_data, _) = read_rom_data(filename, options)?;
+    let mut result = if Cart::is_unif(&raw_data) {
+        Cart::from_unif(&raw_data)?
+    } else if Cart::is_nsf(&raw_data) {
+        Cart::from_nsf(&raw_data)?
+    } else if Cart::is_fds(&raw_data) {
+        let bios_path = rom_path.with_file_name(FDS_BIOS_FILE);
+        match fs::read(&bios_path) {
+            Ok(bios) if !bios.is_empty() => Cart::from_fds(&raw_data, bios)?,
+            _ => return Err(LoadError::MissingBios(bios_path.display().to_string())),
+        }
+    } else {
+        Cart::new(&raw_data)?
+    };
+    result.attach_save_file(&rom_path.to_string_lossy());
+    return Ok(result);
+}
+
+/// Describes a ROM file without loading it, for the `info` command.
+pub fn read_rom_info(filename: &str, options: &LoadOptions) -> Result<RomInfo, LoadError> {
+    let (_, raw_data, patches) = read_rom_data(filename, options)?;
+    let mut result = RomInfo::new(&raw_data)?;
+    result.patches = patches;
+    return Ok(result);
+}
+
+/// Reads a ROM file with its patches applied, along with the path it should
+/// be treated as living at and the patch files used. ROMs inside a ZIP archive are treated as if they
+/// sat next to it, so saves, patches and the FDS BIOS (disksys.rom) are
+/// looked up there under the inner file name. A <rom>.ips or <rom>.bps found
+/// that way is applied to the raw file before any patches from `options`.
+fn read_rom_data(
+    filename: &str,
+    options: &LoadOptions,
+) -> Result<(PathBuf, Vec<u8>, Vec<String>), LoadError> {
+    let mut raw_data = fs::read(filename)?;
+    let mut rom_path = Path::new(filename).to_path_buf();
+    if archive::is_zip(&raw_data) {
+        let (entry, data) = archive::read_entry(&raw_data, options.entry.as_deref())?;
+        let entry_name = Path::new(&entry).file_name().unwrap_or_default();
+        rom_path = rom_path.with_file_name(entry_name);
+        raw_data = data;
+    }
+    let detected = PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .filter(|path| path.is_file());
+    let requested = options.patches.iter().map(PathBuf::from);
+    let mut patches = vec![];
+    for patch_path in detected.chain(requested) {
+        let patch = fs::read(&patch_path)?;
+        raw_data = patch::apply(&raw_data, &patch)?;
+        patches.push(patch_path.display().to_string());
+    }
+    return Ok((rom_path, raw_data, patches));
 }
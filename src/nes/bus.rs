@@ -1,15 +1,23 @@
 use crate::nes::{
-    inturrupts::nmi,
-    ppu::{Ppu, CPU_TO_PPU_CYCLE_SCALE},
+    cpu::status::Status,
+    inturrupts::{irq, nmi},
+    ppu::Ppu,
+    timing::Timing,
 };
 
-use super::{cart::Cart, cpu::ricoh6502::Ricoh6502, inturrupts::reset, memory::Memory};
+use super::{cart::Cart, cpu::ricoh6502::Ricoh6502, inturrupts::reset, io::Io, memory::Memory};
+
+const SAVE_INTERVAL_SECONDS: f64 = 1.0;
 
 pub struct Bus {
     pub cart: Cart,
     pub cpu: Ricoh6502,
     pub ppu: Ppu,
+    pub io: Io,
     pub clock: i64,
+    pub save_timer: i64,
+    pub timing: Timing,
+    pub ppu_remainder: i64,
 }
 
 impl Default for Bus {
@@ -18,7 +26,11 @@ impl Default for Bus {
             cart: Default::default(),
             cpu: Default::default(),
             ppu: Default::default(),
+            io: Default::default(),
             clock: 7,
+            save_timer: 0,
+            timing: Default::default(),
+            ppu_remainder: 0,
         }
     }
 }
@@ -37,11 +49,19 @@ impl Memory for Bus {
 
 impl Bus {
     pub fn new(cart: Cart) -> Self {
+        return Self::with_timing(cart, Default::default());
+    }
+
+    pub fn with_timing(cart: Cart, timing: Timing) -> Self {
         let mut result = Self {
+            io: Io::new(&cart),
             cart: cart,
             cpu: Default::default(),
             ppu: Default::default(),
             clock: 7,
+            save_timer: 0,
+            timing: timing,
+            ppu_remainder: 0,
         };
         reset(&mut result);
         return result;
@@ -52,15 +72,54 @@ impl Bus {
             Ricoh6502::RAM_BEGIN..=Ricoh6502::RAM_END => &mut self.cpu,
             Cart::BEGIN..=Cart::END => &mut self.cart,
             Ppu::BEGIN..=Ppu::END | Ppu::DMA => &mut self.ppu,
+            Io::BEGIN..=Io::END => &mut self.io,
             _ => panic!("invalid memory access at 0x{:04X}", address),
         }
     }
 
     pub fn tick(&mut self, n_cycles: i64) {
         self.clock += n_cycles;
-        self.ppu.tick(n_cycles * CPU_TO_PPU_CYCLE_SCALE);
-        if (self.ppu.is_nmi_interrupt) {
+        let n_dots = self.ppu_dots(n_cycles);
+        self.ppu.tick(n_dots);
+        self.cart.tick(n_cycles);
+        self.io.tick(n_cycles);
+        self.save_timer += n_cycles;
+        if self.save_timer as f64 >= SAVE_INTERVAL_SECONDS * self.timing.cpu_frequency {
+            self.save_timer = 0;
+            self.cart.flush_save();
+        }
+        if self.ppu.poll_nmi() {
             nmi(self);
+        } else if self.cart.is_irq() && !self.cpu.status.contains(Status::InterruptDisable) {
+            irq(self);
         }
     }
+
+    /// Converts CPU cycles to PPU dots, carrying the fraction left over on
+    /// PAL to the next call.
+    pub fn ppu_dots(&mut self, n_cycles: i64) -> i64 {
+        let scaled = n_cycles * self.timing.ppu_dots_per_cycle + self.ppu_remainder;
+        self.ppu_remainder = scaled % self.timing.ppu_cycle_divisor;
+        return scaled / self.timing.ppu_cycle_divisor;
+    }
+
+    pub fn audio_sample(&self) -> f32 {
+        return self.cart.expansion_audio();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::cart::header::Region;
+
+    #[test]
+    fn pal_ppu_dots_carry_remainder() {
+        let mut bus = Bus::default();
+        bus.timing = Timing::new(Region::Pal);
+        let dots: Vec<i64> = (0..5).map(|_| bus.ppu_dots(1)).collect();
+        assert_eq!(dots, [3, 3, 3, 3, 4]);
+        assert_eq!(bus.ppu_remainder, 0);
+        assert_eq!(bus.ppu_dots(5), 16);
+    }
 }
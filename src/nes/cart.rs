@@ -1,21 +1,45 @@
-mod header;
+pub mod archive;
+pub mod audio;
+mod banks;
+mod chr;
+pub mod database;
+mod disk;
+mod eeprom;
+mod flash;
+pub mod header;
+pub mod info;
+mod ips;
+mod load_error;
+pub mod mapper;
+pub mod nsf;
+pub mod patch;
+mod prg_ram;
+mod save;
+mod unif;
 
-use std::os::raw;
+use std::{cell::RefCell, rc::Rc};
 
 use super::memory::Memory;
-use header::{ControlOne, Header};
-
-const HEADER_SIZE: usize = 0x10;
-const TRAINER_SIZE: usize = 512;
-const PRG_BANK_SIZE: usize = 0x4000;
-const CHR_BANK_SIZE: usize = 0x2000;
-const PRG_BEGIN: usize = 0x8000;
+use database::{GameInfo, RomHashes};
+use disk::DiskImage;
+use header::{Header, Region, HEADER_SIZE};
+pub use load_error::LoadError;
+use mapper::{fds::Fds, nsf::NsfMapper, MapperRef};
+use nsf::Nsf;
+use save::SaveFile;
+use unif::Unif;
 
 pub struct Cart {
     pub header: Header,
     pub prg_rom: Vec<u8>,
     pub chr_rom: Vec<u8>,
-    // pub ram: Vec<u8>,
+    pub trainer: Vec<u8>,
+    pub hashes: RomHashes,
+    pub game: Option<&'static GameInfo>,
+    pub corrections: Vec<String>,
+    pub nsf: Option<Nsf>,
+    pub mapper: MapperRef,
+    pub save_file: Option<SaveFile>,
 }
 
 impl Default for Cart {
@@ -24,22 +48,30 @@ impl Default for Cart {
             header: Default::default(),
             prg_rom: Default::default(),
             chr_rom: Default::default(),
+            trainer: Default::default(),
+            hashes: Default::default(),
+            game: None,
+            corrections: vec![],
+            nsf: None,
+            mapper: mapper::empty(),
+            save_file: None,
         }
     }
 }
 
 impl Memory for Cart {
     fn read(&mut self, address: usize) -> u8 {
-        let address = self.map_address(address);
-        let result = self.prg_rom[address];
-        println!("{:02X}", result);
-        return self.prg_rom[address];
+        return self.mapper.borrow_mut().cpu_read(address);
     }
 
     fn write(&mut self, address: usize, value: u8) {
-        if (!self.header.control.control_one.contains(ControlOne::Ram)) {
-            panic!("invalid write to 0x{:04X}", address);
-        }
+        self.mapper.borrow_mut().cpu_write(address, value);
+    }
+}
+
+impl Drop for Cart {
+    fn drop(&mut self) {
+        self.flush_save();
     }
 }
 
@@ -47,28 +79,176 @@ impl Cart {
     pub const BEGIN: usize = 0x4020;
     pub const END: usize = 0xFFFF;
 
-    pub fn new(raw_data: &Vec<u8>) -> Self {
-        let header = Header::new(&raw_data);
-        let prg_size = header.num_prg_banks as usize * PRG_BANK_SIZE;
-        let chr_size = header.num_chr_banks as usize * CHR_BANK_SIZE;
-        let trainer_size = if (header.control.control_one.contains(ControlOne::Trainer)) {
-            TRAINER_SIZE
-        } else {
-            0
+    pub fn new(raw_data: &[u8]) -> Result<Self, LoadError> {
+        let header = Header::from_ines(raw_data)?;
+        let prg_begin = header.prg_rom_begin();
+        let chr_begin = header.chr_rom_begin();
+        let prg_rom = raw_data[prg_begin..chr_begin].to_vec();
+        let chr_rom = raw_data[chr_begin..header.rom_end()].to_vec();
+        let trainer = raw_data[HEADER_SIZE..prg_begin].to_vec();
+        return Self::from_parts(header, prg_rom, chr_rom, trainer);
+    }
+
+    /// Builds a cart from UNIF data. The board name is translated into a
+    /// header so the rest of loading is the same as for iNES files.
+    pub fn from_unif(raw_data: &[u8]) -> Result<Self, LoadError> {
+        let unif = Unif::new(raw_data)?;
+        let mut result = Self::from_parts(unif.header, unif.prg_rom, unif.chr_rom, vec![])?;
+        result.load_chr_ram(&unif.chr_ram);
+        return Ok(result);
+    }
+
+    pub fn is_unif(raw_data: &[u8]) -> bool {
+        return unif::is_unif(raw_data);
+    }
+
+    /// Builds a Famicom Disk System cart from a disk image and the RAM
+    /// adapter's BIOS, which has to come from the user.
+    pub fn from_fds(raw_data: &[u8], bios: Vec<u8>) -> Result<Self, LoadError> {
+        let disk = DiskImage::new(raw_data)?;
+        let hashes = RomHashes::new(raw_data, &[]);
+        let mapper: MapperRef = Rc::new(RefCell::new(Fds::new(
+            bios.clone(),
+            disk,
+            raw_data.to_vec(),
+        )));
+        return Ok(Self {
+            header: Default::default(),
+            prg_rom: bios,
+            chr_rom: vec![],
+            trainer: vec![],
+            hashes: hashes,
+            game: None,
+            corrections: vec![],
+            nsf: None,
+            mapper: mapper,
+            save_file: None,
+        });
+    }
+
+    pub fn is_fds(raw_data: &[u8]) -> bool {
+        return disk::is_fds(raw_data);
+    }
+
+    /// Builds a player cart around an NSF or NSFe rip.
+    pub fn from_nsf(raw_data: &[u8]) -> Result<Self, LoadError> {
+        let nsf = Nsf::new(raw_data)?;
+        let unsupported = NsfMapper::unsupported_expansion(&nsf);
+        if !unsupported.is_none() {
+            let names = nsf::expansion_names(unsupported).join("/");
+            return Err(LoadError::UnsupportedBoard(format!(
+                "NSF with {} audio",
+                names
+            )));
+        }
+        let hashes = RomHashes::new(&nsf.data, &[]);
+        let mapper: MapperRef = Rc::new(RefCell::new(NsfMapper::new(&nsf)));
+        return Ok(Self {
+            header: Default::default(),
+            prg_rom: nsf.data.clone(),
+            chr_rom: vec![],
+            trainer: vec![],
+            hashes: hashes,
+            game: None,
+            corrections: vec![],
+            nsf: Some(nsf),
+            mapper: mapper,
+            save_file: None,
+        });
+    }
+
+    pub fn is_nsf(raw_data: &[u8]) -> bool {
+        return nsf::is_nsf(raw_data);
+    }
+
+    /// The region the cart was made for. NSF rips carry their own.
+    pub fn region(&self) -> Region {
+        return match &self.nsf {
+            Some(nsf) => nsf.region,
+            None => self.header.region(),
         };
-        let prg_begin = HEADER_SIZE + trainer_size;
-        let prg_end = prg_begin + prg_size;
-        let chr_begin = prg_end;
-        let chr_end = chr_begin + chr_size;
-        return Self {
-            header: header,
-            prg_rom: raw_data[prg_begin..prg_end].to_vec(),
-            chr_rom: raw_data[chr_begin..chr_end].to_vec(),
+    }
+
+    pub fn select_track(&mut self, track: usize) {
+        self.mapper.borrow_mut().select_track(track);
+    }
+
+    fn from_parts(
+        mut header: Header,
+        prg_rom: Vec<u8>,
+        chr_rom: Vec<u8>,
+        trainer: Vec<u8>,
+    ) -> Result<Self, LoadError> {
+        let hashes = RomHashes::new(&prg_rom, &chr_rom);
+        let game = database::find(&hashes);
+        let corrections = match game {
+            Some(game) => database::correct_header(&mut header, game),
+            None => vec![],
         };
+        let mapper = mapper::new(&header, prg_rom.to_vec(), chr_rom.to_vec(), &trainer)?;
+        return Ok(Self {
+            header: header,
+            prg_rom: prg_rom,
+            chr_rom: chr_rom,
+            trainer: trainer,
+            hashes: hashes,
+            game: game,
+            corrections: corrections,
+            nsf: None,
+            mapper: mapper,
+            save_file: None,
+        });
+    }
+
+    pub fn attach_save_file(&mut self, rom_path: &str) {
+        let is_diff = self.mapper.borrow().save_base().is_some();
+        let save_file = SaveFile::new(rom_path, is_diff);
+        save_file.load(&mut *self.mapper.borrow_mut());
+        self.save_file = Some(save_file);
+    }
+
+    pub fn flush_save(&mut self) {
+        if let Some(save_file) = &self.save_file {
+            save_file.flush(&mut *self.mapper.borrow_mut());
+        }
+    }
+
+    pub fn tick(&mut self, n_cycles: i64) {
+        self.mapper.borrow_mut().tick(n_cycles);
+    }
+
+    pub fn is_irq(&self) -> bool {
+        return self.mapper.borrow().is_irq();
+    }
+
+    /// CHR-RAM contents for a save state to store alongside the rest of the
+    /// console, and `load_chr_ram` to put them back.
+    pub fn chr_ram(&self) -> Vec<u8> {
+        return self.mapper.borrow_mut().chr_ram().to_vec();
+    }
+
+    /// Ignores data of the wrong size, as from a state saved by another board.
+    pub fn load_chr_ram(&mut self, data: &[u8]) {
+        let mut mapper = self.mapper.borrow_mut();
+        let chr_ram = mapper.chr_ram();
+        if chr_ram.len() == data.len() {
+            chr_ram.copy_from_slice(data);
+        }
+    }
+
+    pub fn num_disk_sides(&self) -> usize {
+        return self.mapper.borrow().num_disk_sides();
+    }
+
+    pub fn insert_disk(&mut self, side: usize) {
+        self.mapper.borrow_mut().insert_disk(Some(side));
+    }
+
+    pub fn eject_disk(&mut self) {
+        self.mapper.borrow_mut().insert_disk(None);
     }
 
-    fn map_address(&self, address: usize) -> usize {
-        let result = (address - PRG_BEGIN) % (self.header.num_prg_banks as usize * PRG_BANK_SIZE);
-        return result;
+    pub fn expansion_audio(&self) -> f32 {
+        return self.mapper.borrow().expansion_audio();
     }
 }
@@ -0,0 +1,50 @@
+use std::{
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use zip::ZipArchive;
+
+use super::load_error::LoadError;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const ROM_EXTENSIONS: [&str; 7] = ["nes", "fds", "qd", "nsf", "nsfe", "unf", "unif"];
+
+pub fn is_zip(data: &[u8]) -> bool {
+    return data.starts_with(ZIP_MAGIC);
+}
+
+/// Pulls a ROM out of a ZIP archive, either the entry with the given name or
+/// the first one with a ROM extension. Returns the entry's name with it.
+pub fn read_entry(data: &[u8], name: Option<&str>) -> Result<(String, Vec<u8>), LoadError> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(invalid)?;
+    let index = match name {
+        Some(name) => archive.index_for_name(name),
+        None => {
+            (0..archive.len()).find(|&index| archive.name_for_index(index).is_some_and(is_rom_name))
+        }
+    };
+    let Some(index) = index else {
+        let reason = match name {
+            Some(name) => format!("{} not found in archive", name),
+            None => "no ROM found in archive".to_string(),
+        };
+        return Err(LoadError::InvalidFormat(reason));
+    };
+    let mut file = archive.by_index(index).map_err(invalid)?;
+    let mut result = vec![];
+    file.read_to_end(&mut result)?;
+    return Ok((file.name().to_string(), result));
+}
+
+fn is_rom_name(name: &str) -> bool {
+    let Some(extension) = Path::new(name).extension() else {
+        return false;
+    };
+    let extension = extension.to_string_lossy().to_lowercase();
+    return ROM_EXTENSIONS.contains(&extension.as_str());
+}
+
+fn invalid(error: zip::result::ZipError) -> LoadError {
+    return LoadError::InvalidFormat(format!("bad ZIP archive: {}", error));
+}
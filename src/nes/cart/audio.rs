@@ -0,0 +1,12 @@
+pub mod fds;
+pub mod sunsoft_5b;
+
+use crate::nes::memory::Memory;
+
+/// A sound chip on the cartridge whose output is mixed with the console's own
+/// audio. Register writes arrive through `Memory::write` at the chip's CPU
+/// addresses.
+pub trait ExpansionAudio: Memory {
+    fn tick(&mut self, n_cycles: i64);
+    fn output(&self) -> f32;
+}
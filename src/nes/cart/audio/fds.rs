@@ -0,0 +1,305 @@
+use crate::nes::memory::Memory;
+
+use super::ExpansionAudio;
+
+const WAVE_TABLE_SIZE: usize = 64;
+const MOD_TABLE_SIZE: usize = 64;
+const MAX_GAIN: u8 = 32;
+const MAX_LEVEL: f32 = 63.0;
+const MASTER_VOLUMES: [u32; 4] = [36, 24, 17, 14];
+const VOLUME_DIVISOR: u32 = 1152;
+const MOD_STEPS: [i8; 8] = [0, 1, 2, 4, 0, -4, -2, -1];
+const MOD_RESET: u8 = 4;
+
+const WAVE_BEGIN: usize = 0x4040;
+const WAVE_END: usize = 0x407F;
+const VOLUME_ENVELOPE: usize = 0x4080;
+const WAVE_FREQUENCY_LOW: usize = 0x4082;
+const WAVE_FREQUENCY_HIGH: usize = 0x4083;
+const MOD_ENVELOPE: usize = 0x4084;
+const MOD_COUNTER: usize = 0x4085;
+const MOD_FREQUENCY_LOW: usize = 0x4086;
+const MOD_FREQUENCY_HIGH: usize = 0x4087;
+const MOD_TABLE: usize = 0x4088;
+const WAVE_WRITE: usize = 0x4089;
+const ENVELOPE_SPEED: usize = 0x408A;
+const VOLUME_GAIN: usize = 0x4090;
+const MOD_GAIN: usize = 0x4092;
+
+const ENVELOPE_SPEED_MASK: u8 = 0x3F;
+const ENVELOPE_INCREASE: u8 = 0x40;
+const ENVELOPE_DISABLE: u8 = 0x80;
+const FREQUENCY_HIGH_MASK: u8 = 0x0F;
+const WAVE_HALT: u8 = 0x80;
+const ENVELOPES_HALT: u8 = 0x40;
+const MOD_HALT: u8 = 0x80;
+const WAVE_WRITE_ENABLE: u8 = 0x80;
+const MASTER_VOLUME_MASK: u8 = 0x03;
+
+/// Volume or modulation envelope. When enabled the gain steps towards 0 or 32
+/// once every 8 * (speed + 1) * master speed CPU cycles.
+pub struct FdsEnvelope {
+    pub speed: u8,
+    pub gain: u8,
+    pub is_increase: bool,
+    pub is_disabled: bool,
+    pub timer: u32,
+}
+
+impl Default for FdsEnvelope {
+    fn default() -> Self {
+        Self {
+            speed: 0,
+            gain: 0,
+            is_increase: false,
+            is_disabled: true,
+            timer: 0,
+        }
+    }
+}
+
+impl FdsEnvelope {
+    fn write(&mut self, value: u8, master_speed: u8) {
+        self.speed = value & ENVELOPE_SPEED_MASK;
+        self.is_increase = value & ENVELOPE_INCREASE != 0;
+        self.is_disabled = value & ENVELOPE_DISABLE != 0;
+        if self.is_disabled {
+            self.gain = self.speed;
+        }
+        self.reset_timer(master_speed);
+    }
+
+    fn reset_timer(&mut self, master_speed: u8) {
+        self.timer = 8 * (self.speed as u32 + 1) * master_speed as u32;
+    }
+
+    fn clock(&mut self, master_speed: u8) {
+        if self.is_disabled || master_speed == 0 {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.reset_timer(master_speed);
+        if self.is_increase && self.gain < MAX_GAIN {
+            self.gain += 1;
+        } else if !self.is_increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+    }
+}
+
+/// The RAM adapter's sound channel: a 64-step, 6-bit wavetable whose pitch is
+/// bent by a second table of modulation steps.
+pub struct FdsAudio {
+    pub wave_table: [u8; WAVE_TABLE_SIZE],
+    pub wave_position: usize,
+    pub wave_accumulator: u16,
+    pub wave_frequency: u16,
+    pub is_wave_halted: bool,
+    pub is_wave_write: bool,
+    pub are_envelopes_halted: bool,
+    pub volume: FdsEnvelope,
+    pub master_volume: u8,
+    pub master_speed: u8,
+    pub modulator: FdsEnvelope,
+    pub mod_table: [u8; MOD_TABLE_SIZE],
+    pub mod_position: usize,
+    pub mod_accumulator: u16,
+    pub mod_frequency: u16,
+    pub mod_counter: i8,
+    pub is_mod_halted: bool,
+    pub output_level: u8,
+}
+
+impl Default for FdsAudio {
+    fn default() -> Self {
+        Self {
+            wave_table: [0; WAVE_TABLE_SIZE],
+            wave_position: 0,
+            wave_accumulator: 0,
+            wave_frequency: 0,
+            is_wave_halted: true,
+            is_wave_write: false,
+            are_envelopes_halted: false,
+            volume: Default::default(),
+            master_volume: 0,
+            master_speed: 0xFF,
+            modulator: Default::default(),
+            mod_table: [0; MOD_TABLE_SIZE],
+            mod_position: 0,
+            mod_accumulator: 0,
+            mod_frequency: 0,
+            mod_counter: 0,
+            is_mod_halted: true,
+            output_level: 0,
+        }
+    }
+}
+
+impl Memory for FdsAudio {
+    fn read(&mut self, address: usize) -> u8 {
+        let result = match address {
+            WAVE_BEGIN..=WAVE_END => self.wave_table[address - WAVE_BEGIN],
+            VOLUME_GAIN => self.volume.gain,
+            MOD_GAIN => self.modulator.gain,
+            _ => 0,
+        };
+        return result;
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        match address {
+            WAVE_BEGIN..=WAVE_END if self.is_wave_write => {
+                self.wave_table[address - WAVE_BEGIN] = value & 0x3F;
+            }
+            VOLUME_ENVELOPE => self.volume.write(value, self.master_speed),
+            WAVE_FREQUENCY_LOW => {
+                self.wave_frequency = (self.wave_frequency & 0x0F00) | value as u16;
+            }
+            WAVE_FREQUENCY_HIGH => {
+                let high = (value & FREQUENCY_HIGH_MASK) as u16;
+                self.wave_frequency = (self.wave_frequency & 0x00FF) | (high << 8);
+                self.is_wave_halted = value & WAVE_HALT != 0;
+                self.are_envelopes_halted = value & ENVELOPES_HALT != 0;
+                if self.is_wave_halted {
+                    self.wave_position = 0;
+                    self.wave_accumulator = 0;
+                }
+                if self.are_envelopes_halted {
+                    self.volume.reset_timer(self.master_speed);
+                    self.modulator.reset_timer(self.master_speed);
+                }
+            }
+            MOD_ENVELOPE => self.modulator.write(value, self.master_speed),
+            MOD_COUNTER => self.set_mod_counter((value & 0x7F) as i16),
+            MOD_FREQUENCY_LOW => {
+                self.mod_frequency = (self.mod_frequency & 0x0F00) | value as u16;
+            }
+            MOD_FREQUENCY_HIGH => {
+                let high = (value & FREQUENCY_HIGH_MASK) as u16;
+                self.mod_frequency = (self.mod_frequency & 0x00FF) | (high << 8);
+                self.is_mod_halted = value & MOD_HALT != 0;
+                if self.is_mod_halted {
+                    self.mod_accumulator = 0;
+                }
+            }
+            MOD_TABLE if self.is_mod_halted => {
+                let step = value & 0x07;
+                self.mod_table[self.mod_position] = step;
+                self.mod_table[(self.mod_position + 1) % MOD_TABLE_SIZE] = step;
+                self.mod_position = (self.mod_position + 2) % MOD_TABLE_SIZE;
+            }
+            WAVE_WRITE => {
+                self.is_wave_write = value & WAVE_WRITE_ENABLE != 0;
+                self.master_volume = value & MASTER_VOLUME_MASK;
+            }
+            ENVELOPE_SPEED => self.master_speed = value,
+            _ => {}
+        }
+    }
+}
+
+impl ExpansionAudio for FdsAudio {
+    fn tick(&mut self, n_cycles: i64) {
+        for _ in 0..n_cycles {
+            self.clock();
+        }
+    }
+
+    fn output(&self) -> f32 {
+        return self.output_level as f32 / MAX_LEVEL;
+    }
+}
+
+impl FdsAudio {
+    pub const BEGIN: usize = 0x4040;
+    pub const END: usize = 0x4097;
+
+    fn clock(&mut self) {
+        if !self.is_wave_halted && !self.are_envelopes_halted {
+            self.volume.clock(self.master_speed);
+            self.modulator.clock(self.master_speed);
+        }
+        self.clock_modulator();
+        if self.is_wave_write {
+            return;
+        }
+        if !self.is_wave_halted {
+            let pitch = self.pitch();
+            if pitch > 0 {
+                let (accumulator, is_overflow) =
+                    self.wave_accumulator.overflowing_add(pitch as u16);
+                self.wave_accumulator = accumulator;
+                if is_overflow {
+                    self.wave_position = (self.wave_position + 1) % WAVE_TABLE_SIZE;
+                }
+            }
+        }
+        self.update_output();
+    }
+
+    fn clock_modulator(&mut self) {
+        if self.is_mod_halted || self.mod_frequency == 0 {
+            return;
+        }
+        let (accumulator, is_overflow) = self.mod_accumulator.overflowing_add(self.mod_frequency);
+        self.mod_accumulator = accumulator;
+        if !is_overflow {
+            return;
+        }
+        let step = self.mod_table[self.mod_position];
+        if step == MOD_RESET {
+            self.set_mod_counter(0);
+        } else {
+            self.set_mod_counter(self.mod_counter as i16 + MOD_STEPS[step as usize] as i16);
+        }
+        self.mod_position = (self.mod_position + 1) % MOD_TABLE_SIZE;
+    }
+
+    /// Keeps the modulation counter in its 7-bit signed range.
+    fn set_mod_counter(&mut self, value: i16) {
+        let mut value = value;
+        if value >= 64 {
+            value -= 128;
+        } else if value < -64 {
+            value += 128;
+        }
+        self.mod_counter = value as i8;
+    }
+
+    /// Wave frequency after modulation, using the rounding the hardware does.
+    fn pitch(&self) -> i32 {
+        let frequency = self.wave_frequency as i32;
+        if self.is_mod_halted {
+            return frequency;
+        }
+        let counter = self.mod_counter as i32;
+        let mut temp = counter * self.modulator.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+        temp *= frequency;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+        return frequency + temp;
+    }
+
+    fn update_output(&mut self) {
+        let gain = self.volume.gain.min(MAX_GAIN) as u32;
+        let level = gain * MASTER_VOLUMES[self.master_volume as usize];
+        let sample = self.wave_table[self.wave_position] as u32;
+        self.output_level = (sample * level / VOLUME_DIVISOR) as u8;
+    }
+}
@@ -0,0 +1,283 @@
+use crate::nes::memory::Memory;
+
+use super::ExpansionAudio;
+
+const NUM_CHANNELS: usize = 3;
+const NUM_REGISTERS: usize = 16;
+const CLOCK_DIVIDER: i64 = 16;
+const MAX_LEVEL: u8 = 31;
+const DECIBELS_PER_LEVEL: f32 = 1.5;
+
+const NOISE_PERIOD: usize = 0x06;
+const CHANNEL_DISABLE: usize = 0x07;
+const CHANNEL_VOLUME: usize = 0x08;
+const ENVELOPE_PERIOD_LOW: usize = 0x0B;
+const ENVELOPE_PERIOD_HIGH: usize = 0x0C;
+const ENVELOPE_SHAPE: usize = 0x0D;
+
+const VOLUME_MASK: u8 = 0x0F;
+const USE_ENVELOPE: u8 = 0x10;
+
+const SHAPE_HOLD: u8 = 0b0001;
+const SHAPE_ALTERNATE: u8 = 0b0010;
+const SHAPE_ATTACK: u8 = 0b0100;
+const SHAPE_CONTINUE: u8 = 0b1000;
+
+pub struct Tone {
+    pub counter: u16,
+    pub output: bool,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            counter: 0,
+            output: false,
+        }
+    }
+}
+
+pub struct Noise {
+    pub counter: u8,
+    pub half_period: bool,
+    pub shift_register: u32,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self {
+            counter: 0,
+            half_period: false,
+            shift_register: 1,
+        }
+    }
+}
+
+impl Noise {
+    fn output(&self) -> bool {
+        return self.shift_register & 1 != 0;
+    }
+
+    fn clock(&mut self, period: u8) {
+        self.counter += 1;
+        if self.counter < period.max(1) {
+            return;
+        }
+        self.counter = 0;
+        self.half_period = !self.half_period;
+        if self.half_period {
+            return;
+        }
+        let feedback = (self.shift_register ^ (self.shift_register >> 3)) & 1;
+        self.shift_register = (self.shift_register >> 1) | (feedback << 16);
+    }
+}
+
+pub struct Envelope {
+    pub counter: u16,
+    pub step: u8,
+    pub shape: u8,
+    pub attack: bool,
+    pub holding: bool,
+    pub hold_level: u8,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            counter: 0,
+            step: 0,
+            shape: 0,
+            attack: false,
+            holding: true,
+            hold_level: 0,
+        }
+    }
+}
+
+impl Envelope {
+    fn restart(&mut self, shape: u8) {
+        self.shape = shape;
+        self.counter = 0;
+        self.step = 0;
+        self.attack = shape & SHAPE_ATTACK != 0;
+        self.holding = false;
+    }
+
+    fn level(&self) -> u8 {
+        if self.holding {
+            return self.hold_level;
+        }
+        return if self.attack {
+            self.step
+        } else {
+            MAX_LEVEL - self.step
+        };
+    }
+
+    fn clock(&mut self, period: u16) {
+        if self.holding {
+            return;
+        }
+        self.counter += 1;
+        if self.counter < period.max(1) {
+            return;
+        }
+        self.counter = 0;
+        if self.step < MAX_LEVEL {
+            self.step += 1;
+            return;
+        }
+        self.end_of_cycle();
+    }
+
+    fn end_of_cycle(&mut self) {
+        let is_continue = self.shape & SHAPE_CONTINUE != 0;
+        let is_hold = self.shape & SHAPE_HOLD != 0;
+        let is_alternate = self.shape & SHAPE_ALTERNATE != 0;
+        let is_attack = self.shape & SHAPE_ATTACK != 0;
+        self.step = 0;
+        if !is_continue {
+            self.holding = true;
+            self.hold_level = 0;
+        } else if is_hold {
+            self.holding = true;
+            self.hold_level = if is_attack != is_alternate {
+                MAX_LEVEL
+            } else {
+                0
+            };
+        } else if is_alternate {
+            self.attack = !self.attack;
+        }
+    }
+}
+
+/// The Sunsoft 5B: an FME-7 with a YM2149F-style sound core bolted on. Three
+/// square channels that can each mix in a shared noise generator and envelope.
+pub struct Sunsoft5b {
+    pub register_select: u8,
+    pub registers: [u8; NUM_REGISTERS],
+    pub divider: i64,
+    pub tones: [Tone; NUM_CHANNELS],
+    pub noise: Noise,
+    pub envelope: Envelope,
+    pub levels: [f32; MAX_LEVEL as usize + 1],
+}
+
+impl Default for Sunsoft5b {
+    fn default() -> Self {
+        let mut levels = [0.0; MAX_LEVEL as usize + 1];
+        for level in 1..=MAX_LEVEL {
+            let decibels = (MAX_LEVEL - level) as f32 * -DECIBELS_PER_LEVEL;
+            levels[level as usize] = 10.0_f32.powf(decibels / 20.0);
+        }
+        Self {
+            register_select: 0,
+            registers: [0; NUM_REGISTERS],
+            divider: 0,
+            tones: Default::default(),
+            noise: Default::default(),
+            envelope: Default::default(),
+            levels: levels,
+        }
+    }
+}
+
+impl Memory for Sunsoft5b {
+    fn read(&mut self, address: usize) -> u8 {
+        return 0;
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        match address {
+            Self::SELECT_BEGIN..=Self::SELECT_END => self.register_select = value & 0x0F,
+            Self::WRITE_BEGIN..=Self::WRITE_END => self.register_write(value),
+            _ => panic!("invalid 5B write to 0x{:04X}", address),
+        }
+    }
+}
+
+impl ExpansionAudio for Sunsoft5b {
+    fn tick(&mut self, n_cycles: i64) {
+        for _ in 0..n_cycles {
+            self.divider += 1;
+            if self.divider < CLOCK_DIVIDER {
+                continue;
+            }
+            self.divider = 0;
+            self.clock();
+        }
+    }
+
+    fn output(&self) -> f32 {
+        let mut result = 0.0;
+        for channel in 0..NUM_CHANNELS {
+            result += self.channel_output(channel);
+        }
+        return result / NUM_CHANNELS as f32;
+    }
+}
+
+impl Sunsoft5b {
+    pub const SELECT_BEGIN: usize = 0xC000;
+    pub const SELECT_END: usize = 0xDFFF;
+    pub const WRITE_BEGIN: usize = 0xE000;
+    pub const WRITE_END: usize = 0xFFFF;
+
+    fn register_write(&mut self, value: u8) {
+        let register = self.register_select as usize;
+        self.registers[register] = value;
+        if register == ENVELOPE_SHAPE {
+            self.envelope.restart(value & 0x0F);
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        let low = self.registers[channel * 2] as u16;
+        let high = (self.registers[channel * 2 + 1] & 0x0F) as u16;
+        return (high << 8) | low;
+    }
+
+    fn envelope_period(&self) -> u16 {
+        let low = self.registers[ENVELOPE_PERIOD_LOW];
+        let high = self.registers[ENVELOPE_PERIOD_HIGH];
+        return u16::from_le_bytes([low, high]);
+    }
+
+    fn clock(&mut self) {
+        for channel in 0..NUM_CHANNELS {
+            let period = self.tone_period(channel).max(1);
+            let tone = &mut self.tones[channel];
+            tone.counter += 1;
+            if tone.counter >= period {
+                tone.counter = 0;
+                tone.output = !tone.output;
+            }
+        }
+        let noise_period = self.registers[NOISE_PERIOD] & 0x1F;
+        self.noise.clock(noise_period);
+        let envelope_period = self.envelope_period();
+        self.envelope.clock(envelope_period);
+    }
+
+    fn channel_output(&self, channel: usize) -> f32 {
+        let disable = self.registers[CHANNEL_DISABLE];
+        let is_tone_disabled = disable & (1 << channel) != 0;
+        let is_noise_disabled = disable & (1 << (channel + 3)) != 0;
+        let is_tone_high = self.tones[channel].output || is_tone_disabled;
+        let is_noise_high = self.noise.output() || is_noise_disabled;
+        if !(is_tone_high && is_noise_high) {
+            return 0.0;
+        }
+        let volume = self.registers[CHANNEL_VOLUME + channel];
+        let level = if volume & USE_ENVELOPE != 0 {
+            self.envelope.level()
+        } else if volume & VOLUME_MASK == 0 {
+            0
+        } else {
+            (volume & VOLUME_MASK) * 2 + 1
+        };
+        return self.levels[level as usize];
+    }
+}
@@ -0,0 +1,55 @@
+/// Maps a CPU or PPU address window onto a switchable bank of cartridge memory.
+/// Every window has the same size, and bank numbers wrap around the memory size
+/// the way the unconnected upper bank lines do on real boards.
+pub struct Banks {
+    pub window_size: usize,
+    pub num_banks: usize,
+    pub offsets: Vec<usize>,
+}
+
+impl Default for Banks {
+    fn default() -> Self {
+        Self {
+            window_size: 1,
+            num_banks: 1,
+            offsets: vec![0],
+        }
+    }
+}
+
+impl Banks {
+    pub fn new(memory_size: usize, window_size: usize, num_windows: usize) -> Self {
+        let num_banks = (memory_size / window_size).max(1);
+        let mut result = Self {
+            window_size: window_size,
+            num_banks: num_banks,
+            offsets: vec![0; num_windows],
+        };
+        for window in 0..num_windows {
+            result.set(window, window);
+        }
+        return result;
+    }
+
+    pub fn set(&mut self, window: usize, bank: usize) {
+        let bank = bank % self.num_banks;
+        self.offsets[window] = bank * self.window_size;
+    }
+
+    pub fn set_last(&mut self, window: usize) {
+        let last = self.last_bank();
+        self.set(window, last);
+    }
+
+    pub fn last_bank(&self) -> usize {
+        return self.num_banks - 1;
+    }
+
+    /// Translates an address relative to the start of the banked region into
+    /// an index into the backing memory.
+    pub fn map(&self, address: usize) -> usize {
+        let window = (address / self.window_size) % self.offsets.len();
+        let offset = address % self.window_size;
+        return self.offsets[window] + offset;
+    }
+}
@@ -0,0 +1,80 @@
+use crate::nes::memory::Memory;
+
+/// Pattern table memory on the cartridge. CHR-ROM comes first and any CHR-RAM
+/// follows it, so mappers bank across both with one index and writes only land
+/// in the RAM part.
+pub struct Chr {
+    pub data: Vec<u8>,
+    pub rom_size: usize,
+}
+
+impl Default for Chr {
+    fn default() -> Self {
+        Self {
+            data: vec![],
+            rom_size: 0,
+        }
+    }
+}
+
+impl Memory for Chr {
+    fn read(&mut self, address: usize) -> u8 {
+        if self.data.is_empty() {
+            return 0;
+        }
+        return self.data[address % self.data.len()];
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        if self.data.is_empty() {
+            return;
+        }
+        let index = address % self.data.len();
+        if index >= self.rom_size {
+            self.data[index] = value;
+        }
+    }
+}
+
+impl Chr {
+    pub fn new(chr_rom: Vec<u8>, ram_size: usize) -> Self {
+        let rom_size = chr_rom.len();
+        let mut data = chr_rom;
+        data.resize(rom_size + ram_size, 0);
+        return Self {
+            data: data,
+            rom_size: rom_size,
+        };
+    }
+
+    pub fn ram(&mut self) -> &mut [u8] {
+        return &mut self.data[self.rom_size..];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.data.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_is_read_only() {
+        let mut chr = Chr::new(vec![0x11; 0x2000], 0);
+        chr.write(0x0010, 0x22);
+        assert_eq!(chr.read(0x0010), 0x11);
+        assert!(chr.ram().is_empty());
+    }
+
+    #[test]
+    fn ram_follows_rom() {
+        let mut chr = Chr::new(vec![0x11; 0x2000], 0x2000);
+        chr.write(0x2010, 0x22);
+        assert_eq!(chr.len(), 0x4000);
+        assert_eq!(chr.ram()[0x0010], 0x22);
+        chr.ram()[0x0020] = 0x33;
+        assert_eq!(chr.read(0x2020), 0x33);
+    }
+}
@@ -0,0 +1,279 @@
+use std::{borrow::Cow, fs, path::Path, sync::OnceLock};
+
+use crate::nes::ppu::VramMirror;
+
+use super::{
+    header::{Header, Region},
+    load_error::LoadError,
+    mapper,
+};
+
+const ROM_EXTENSION: &str = ".nes";
+
+/// Known-good board information for a dump, keyed by the CRC32 of its PRG and
+/// CHR data. Lets us load ROMs whose iNES headers were written by hand.
+pub struct GameInfo {
+    pub crc32: u32,
+    /// Lowercase hex SHA-1 of the same data, or empty if we don't know it.
+    pub sha1: Cow<'static, str>,
+    pub title: Cow<'static, str>,
+    pub region: Region,
+    /// The PCB name where we know it, otherwise the mapper's usual name.
+    pub board: Cow<'static, str>,
+    pub mapper: u8,
+    pub submapper: u8,
+    /// None when the board's mirroring is under mapper control.
+    pub mirroring: Option<VramMirror>,
+    pub prg_ram_size: usize,
+    pub chr_ram_size: usize,
+    pub has_battery: bool,
+}
+
+impl GameInfo {
+    /// A CRC32 match is only trusted when the SHA-1 agrees as well, for the
+    /// entries that have one.
+    fn matches(&self, hashes: &RomHashes) -> bool {
+        return self.crc32 == hashes.crc32 && (self.sha1.is_empty() || self.sha1 == hashes.sha1);
+    }
+}
+
+/// The only entries built in. Anything else needs nes20db.xml, which isn't
+/// shipped here, loaded through `load`.
+const GAMES: &[GameInfo] = &[GameInfo {
+    crc32: 0x3337EC46,
+    sha1: Cow::Borrowed(""),
+    title: Cow::Borrowed("Super Mario Bros."),
+    region: Region::Ntsc,
+    board: Cow::Borrowed("NES-NROM-256"),
+    mapper: 0,
+    submapper: 0,
+    mirroring: Some(VramMirror::Vertical),
+    prg_ram_size: 0,
+    chr_ram_size: 0,
+    has_battery: false,
+}];
+
+/// Games read from an NES 2.0 XML database by `load`, searched before the
+/// built-in entries.
+static LOADED_GAMES: OnceLock<Vec<GameInfo>> = OnceLock::new();
+
+pub struct RomHashes {
+    pub crc32: u32,
+    pub sha1: String,
+}
+
+impl Default for RomHashes {
+    fn default() -> Self {
+        Self {
+            crc32: 0,
+            sha1: String::new(),
+        }
+    }
+}
+
+impl RomHashes {
+    pub fn new(prg_rom: &[u8], chr_rom: &[u8]) -> Self {
+        let mut crc32 = crc32fast::Hasher::new();
+        crc32.update(prg_rom);
+        crc32.update(chr_rom);
+        let mut sha1 = sha1_smol::Sha1::new();
+        sha1.update(prg_rom);
+        sha1.update(chr_rom);
+        return Self {
+            crc32: crc32.finalize(),
+            sha1: sha1.digest().to_string(),
+        };
+    }
+}
+
+pub fn find(hashes: &RomHashes) -> Option<&'static GameInfo> {
+    let loaded = LOADED_GAMES.get().map(Vec::as_slice).unwrap_or_default();
+    return loaded
+        .iter()
+        .chain(GAMES.iter())
+        .find(|game| game.matches(hashes));
+}
+
+/// Loads the NES 2.0 XML database (nes20db.xml) so dumps beyond the built-in
+/// entries get their headers corrected. Only the first load takes effect.
+/// Returns the number of games read.
+pub fn load(path: &Path) -> Result<usize, LoadError> {
+    let xml = fs::read_to_string(path)?;
+    let games = parse(&xml);
+    let result = games.len();
+    let _ = LOADED_GAMES.set(games);
+    return Ok(result);
+}
+
+/// Reads every `<game>` element that has a whole-ROM CRC32. The title comes
+/// from the file name comment each entry starts with.
+fn parse(xml: &str) -> Vec<GameInfo> {
+    let mut result = vec![];
+    for block in xml.split("<game>").skip(1) {
+        let block = block.split("</game>").next().unwrap_or_default();
+        if let Some(game) = parse_game(block) {
+            result.push(game);
+        }
+    }
+    return result;
+}
+
+fn parse_game(block: &str) -> Option<GameInfo> {
+    let crc32 = u32::from_str_radix(attribute(block, "rom", "crc32")?, 16).ok()?;
+    let sha1 = attribute(block, "rom", "sha1").unwrap_or_default();
+    let number = |tag: &str, name: &str| {
+        let value = attribute(block, tag, name).and_then(|value| value.parse().ok());
+        return value.unwrap_or(0usize);
+    };
+    // Mapper numbers above 255 only fit in an NES 2.0 header, which we can't
+    // rewrite into yet.
+    let mapper = u8::try_from(number("pcb", "mapper")).ok()?;
+    let mirroring = match attribute(block, "pcb", "mirroring") {
+        Some("H") => Some(VramMirror::Horizontal),
+        Some("V") => Some(VramMirror::Vertical),
+        Some("4") => Some(VramMirror::FourScreen),
+        _ => None,
+    };
+    let region = match number("console", "region") {
+        1 => Region::Pal,
+        2 => Region::Multi,
+        3 => Region::Dendy,
+        _ => Region::Ntsc,
+    };
+    let title = comment(block).unwrap_or_default();
+    return Some(GameInfo {
+        crc32: crc32,
+        sha1: Cow::Owned(sha1.to_ascii_lowercase()),
+        title: Cow::Owned(title.trim_end_matches(ROM_EXTENSION).to_string()),
+        region: region,
+        board: Cow::Borrowed(mapper::name(mapper).unwrap_or_default()),
+        mapper: mapper,
+        submapper: number("pcb", "submapper") as u8,
+        mirroring: mirroring,
+        prg_ram_size: number("prgram", "size") + number("prgnvram", "size"),
+        chr_ram_size: number("chrram", "size") + number("chrnvram", "size"),
+        has_battery: attribute(block, "pcb", "battery") == Some("1"),
+    });
+}
+
+/// The value of `name` on the first `<tag .../>` element in a game block.
+fn attribute<'a>(block: &'a str, tag: &str, name: &str) -> Option<&'a str> {
+    let begin = block.find(&format!("<{} ", tag))?;
+    let element = &block[begin..];
+    let element = &element[..element.find('>')?];
+    let key = format!(" {}=\"", name);
+    let value = &element[element.find(&key)? + key.len()..];
+    return Some(&value[..value.find('"')?]);
+}
+
+fn comment(block: &str) -> Option<&str> {
+    let begin = block.find("<!--")? + "<!--".len();
+    let end = begin + block[begin..].find("-->")?;
+    return Some(block[begin..end].trim());
+}
+
+/// Rewrites any header fields that disagree with the database entry and
+/// returns a description of each change for the frontend to show.
+pub fn correct_header(header: &mut Header, game: &GameInfo) -> Vec<String> {
+    let mut result = vec![];
+    let mapper = header.control.get_mapper();
+    let submapper = header.submapper();
+    if mapper != game.mapper || (header.is_nes2() && submapper != game.submapper) {
+        result.push(format!(
+            "mapper {}.{} corrected to {}.{}",
+            mapper, submapper, game.mapper, game.submapper
+        ));
+        header.set_mapper(game.mapper, game.submapper);
+    }
+    let mirroring = header.mirroring();
+    if let Some(game_mirroring) = game.mirroring {
+        if mirroring != game_mirroring {
+            result.push(format!(
+                "mirroring {:?} corrected to {:?}",
+                mirroring, game_mirroring
+            ));
+            header.set_mirroring(game_mirroring);
+        }
+    }
+    if header.has_battery() != game.has_battery {
+        result.push(format!("battery corrected to {}", game.has_battery));
+        header.set_battery(game.has_battery);
+    }
+    let prg_ram_size = header.prg_ram_size();
+    let chr_ram_size = header.chr_ram_size();
+    let is_prg_ram_wrong = prg_ram_size != game.prg_ram_size && header.is_nes2();
+    if is_prg_ram_wrong || chr_ram_size != game.chr_ram_size {
+        result.push(format!(
+            "PRG-RAM/CHR-RAM {}/{} bytes corrected to {}/{}",
+            prg_ram_size, chr_ram_size, game.prg_ram_size, game.chr_ram_size
+        ));
+        header.set_ram_sizes(game.prg_ram_size, game.chr_ram_size);
+    }
+    if header.region() != game.region {
+        result.push(format!(
+            "region {:?} corrected to {:?}",
+            header.region(),
+            game.region
+        ));
+        header.set_region(game.region);
+    }
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<nes20db>
+<game>
+	<!-- Some Game (USA).nes -->
+	<prgrom size="131072" crc32="11111111" sha1="AAAA"/>
+	<prgram size="8192"/>
+	<rom size="131072" crc32="1A2B3C4D" sha1="0123456789ABCDEF0123456789ABCDEF01234567"/>
+	<pcb mapper="1" submapper="0" mirroring="V" battery="1"/>
+	<console type="0" region="1"/>
+</game>
+<game>
+	<!-- Too Big Mapper.nes -->
+	<rom size="32768" crc32="00000001" sha1="00"/>
+	<pcb mapper="342" submapper="0" mirroring="H" battery="0"/>
+</game>
+</nes20db>
+"#;
+
+    #[test]
+    fn parse_nes20db() {
+        let games = parse(XML);
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.crc32, 0x1A2B3C4D);
+        assert_eq!(game.sha1, "0123456789abcdef0123456789abcdef01234567");
+        assert_eq!(game.title, "Some Game (USA)");
+        assert!(game.region == Region::Pal);
+        assert_eq!(game.mapper, 1);
+        assert_eq!(game.board, "MMC1");
+        assert!(game.mirroring == Some(VramMirror::Vertical));
+        assert_eq!(game.prg_ram_size, 0x2000);
+        assert_eq!(game.chr_ram_size, 0);
+        assert!(game.has_battery);
+    }
+
+    #[test]
+    fn sha1_confirms_crc32() {
+        let game = &parse(XML)[0];
+        let mut hashes = RomHashes {
+            crc32: 0x1A2B3C4D,
+            sha1: "0123456789abcdef0123456789abcdef01234567".to_string(),
+        };
+        assert!(game.matches(&hashes));
+        hashes.sha1 = "ffffffffffffffffffffffffffffffffffffffff".to_string();
+        assert!(!game.matches(&hashes));
+
+        // Entries without a SHA-1 fall back to the CRC32 alone.
+        assert!(GAMES[0].matches(&RomHashes {
+            crc32: GAMES[0].crc32,
+            sha1: hashes.sha1.clone(),
+        }));
+    }
+}
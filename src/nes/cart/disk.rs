@@ -0,0 +1,305 @@
+use super::load_error::LoadError;
+
+const FDS_MAGIC: &[u8] = b"FDS\x1A";
+const FDS_HEADER_SIZE: usize = 0x10;
+const DISK_INFO_MAGIC: &[u8] = b"\x01*NINTENDO-HVC*";
+const SIDE_SIZE: usize = 65500;
+const QD_SIDE_SIZE: usize = 0x10000;
+const CRC_SIZE: usize = 2;
+
+const LEAD_IN_GAP: usize = 28300 / 8;
+const BLOCK_GAP: usize = 976 / 8;
+const GAP_END: u8 = 0x80;
+
+const BLOCK_DISK_INFO: u8 = 1;
+const BLOCK_FILE_AMOUNT: u8 = 2;
+const BLOCK_FILE_HEADER: u8 = 3;
+const BLOCK_FILE_DATA: u8 = 4;
+const DISK_INFO_SIZE: usize = 56;
+const FILE_AMOUNT_SIZE: usize = 2;
+const FILE_HEADER_SIZE: usize = 16;
+const FILE_SIZE_OFFSET: usize = 13;
+
+const CRC_POLYNOMIAL: u16 = 0x8408;
+
+/// Famicom Disk System disk sides laid out the way the drive head sees them:
+/// a lead-in gap, then each block preceded by a gap end marker and followed by
+/// its CRC and another gap. Every side takes up `SIDE_CAPACITY` bytes so
+/// games have room to append files.
+pub struct DiskImage {
+    pub data: Vec<u8>,
+    pub num_sides: usize,
+    /// Where the sides sit in the file the image was loaded from, so
+    /// `write_file` can put them back.
+    pub header_size: usize,
+    pub side_size: usize,
+}
+
+pub fn is_fds(data: &[u8]) -> bool {
+    return data.starts_with(FDS_MAGIC) || data.starts_with(DISK_INFO_MAGIC);
+}
+
+impl DiskImage {
+    pub const SIDE_CAPACITY: usize = 68000;
+
+    /// Accepts .fds images, with or without the 16-byte header, and raw QD
+    /// dumps whose blocks already carry their CRCs.
+    pub fn new(data: &[u8]) -> Result<Self, LoadError> {
+        let header_size = if data.starts_with(FDS_MAGIC) {
+            FDS_HEADER_SIZE.min(data.len())
+        } else {
+            0
+        };
+        let data = &data[header_size..];
+        let is_qd = data.len() % SIDE_SIZE != 0 && data.len() % QD_SIDE_SIZE == 0;
+        let side_size = if is_qd { QD_SIDE_SIZE } else { SIDE_SIZE };
+        let num_sides = data.len() / side_size;
+        if num_sides == 0 {
+            return Err(LoadError::InvalidFormat("empty FDS image".to_string()));
+        }
+        let mut result = vec![];
+        for side in data.chunks_exact(side_size) {
+            if !side.starts_with(DISK_INFO_MAGIC) {
+                return Err(LoadError::InvalidFormat(
+                    "missing FDS disk info block".to_string(),
+                ));
+            }
+            let mut stream = build_side(side, is_qd);
+            stream.resize(Self::SIDE_CAPACITY, 0);
+            result.extend_from_slice(&stream);
+        }
+        return Ok(Self {
+            data: result,
+            num_sides: num_sides,
+            header_size: header_size,
+            side_size: side_size,
+        });
+    }
+
+    /// Writes the blocks on each side back into `file`, laid out as in the
+    /// file the image came from. Bytes after the last block are left alone.
+    pub fn write_file(&self, file: &mut [u8]) {
+        let has_crc = self.side_size == QD_SIDE_SIZE;
+        for (side, stream) in self.data.chunks_exact(Self::SIDE_CAPACITY).enumerate() {
+            let blocks = read_side(stream, has_crc);
+            let begin = self.header_size + side * self.side_size;
+            let length = blocks.len().min(self.side_size);
+            file[begin..begin + length].copy_from_slice(&blocks[..length]);
+        }
+    }
+}
+
+fn build_side(side: &[u8], has_crc: bool) -> Vec<u8> {
+    let mut result = vec![0; LEAD_IN_GAP];
+    let mut offset = 0;
+    let mut file_size = 0;
+    while offset < side.len() {
+        let Some(size) = block_size(side[offset], file_size) else {
+            break;
+        };
+        if offset + size > side.len() {
+            break;
+        }
+        let block = &side[offset..offset + size];
+        if block[0] == BLOCK_FILE_HEADER {
+            let low = block[FILE_SIZE_OFFSET] as usize;
+            let high = block[FILE_SIZE_OFFSET + 1] as usize;
+            file_size = (high << 8) | low;
+        }
+        result.push(GAP_END);
+        result.extend_from_slice(block);
+        result.extend_from_slice(&block_crc(block).to_le_bytes());
+        result.extend(std::iter::repeat(0).take(BLOCK_GAP));
+        offset += size;
+        if has_crc {
+            offset += CRC_SIZE;
+        }
+    }
+    return result;
+}
+
+/// Takes the blocks back out of a side's stream, dropping the gaps. The CRCs
+/// are kept only for formats that store them.
+fn read_side(stream: &[u8], has_crc: bool) -> Vec<u8> {
+    let mut result = vec![];
+    let mut offset = 0;
+    let mut file_size = 0;
+    loop {
+        while offset < stream.len() && stream[offset] == 0 {
+            offset += 1;
+        }
+        if offset + 1 >= stream.len() || stream[offset] != GAP_END {
+            break;
+        }
+        offset += 1;
+        let Some(size) = block_size(stream[offset], file_size) else {
+            break;
+        };
+        if offset + size + CRC_SIZE > stream.len() {
+            break;
+        }
+        let block = &stream[offset..offset + size];
+        if block[0] == BLOCK_FILE_HEADER {
+            let low = block[FILE_SIZE_OFFSET] as usize;
+            let high = block[FILE_SIZE_OFFSET + 1] as usize;
+            file_size = (high << 8) | low;
+        }
+        result.extend_from_slice(block);
+        offset += size;
+        if has_crc {
+            result.extend_from_slice(&stream[offset..offset + CRC_SIZE]);
+        }
+        offset += CRC_SIZE;
+    }
+    return result;
+}
+
+/// The size of a block from its type byte. File data blocks take their size
+/// from the file header before them.
+fn block_size(block_type: u8, file_size: usize) -> Option<usize> {
+    let result = match block_type {
+        BLOCK_DISK_INFO => DISK_INFO_SIZE,
+        BLOCK_FILE_AMOUNT => FILE_AMOUNT_SIZE,
+        BLOCK_FILE_HEADER => FILE_HEADER_SIZE,
+        BLOCK_FILE_DATA => 1 + file_size,
+        _ => return None,
+    };
+    return Some(result);
+}
+
+fn block_crc(block: &[u8]) -> u16 {
+    let mut result = 0x8000;
+    for &value in block.iter().chain([0, 0].iter()) {
+        result = update_crc(result, value);
+    }
+    return result;
+}
+
+/// Shifts one byte into the drive's CRC-16, least significant bit first.
+pub fn update_crc(crc: u16, value: u8) -> u16 {
+    let mut result = crc;
+    for bit in 0..8 {
+        let carry = result & 1 != 0;
+        result = (result >> 1) | ((((value >> bit) & 1) as u16) << 15);
+        if carry {
+            result ^= CRC_POLYNOMIAL;
+        }
+    }
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_DATA: [u8; 4] = [BLOCK_FILE_DATA, 0x11, 0x22, 0x33];
+
+    fn blocks() -> Vec<Vec<u8>> {
+        let mut disk_info = DISK_INFO_MAGIC.to_vec();
+        disk_info.resize(DISK_INFO_SIZE, 0);
+        let mut file_header = vec![0; FILE_HEADER_SIZE];
+        file_header[0] = BLOCK_FILE_HEADER;
+        file_header[FILE_SIZE_OFFSET] = (FILE_DATA.len() - 1) as u8;
+        return vec![
+            disk_info,
+            vec![BLOCK_FILE_AMOUNT, 1],
+            file_header,
+            FILE_DATA.to_vec(),
+        ];
+    }
+
+    fn side() -> Vec<u8> {
+        let mut result = blocks().concat();
+        result.resize(SIDE_SIZE, 0);
+        return result;
+    }
+
+    #[test]
+    fn lays_out_blocks() {
+        let disk = DiskImage::new(&side()).unwrap();
+        assert_eq!(disk.num_sides, 1);
+        assert_eq!(disk.data.len(), DiskImage::SIDE_CAPACITY);
+        assert!(disk.data[..LEAD_IN_GAP].iter().all(|&value| value == 0));
+        assert_eq!(disk.data[LEAD_IN_GAP], GAP_END);
+        assert!(disk.data[LEAD_IN_GAP + 1..].starts_with(DISK_INFO_MAGIC));
+
+        // Each block is followed by its CRC and a gap before the next one.
+        let block_size = 1 + DISK_INFO_SIZE + CRC_SIZE + BLOCK_GAP;
+        let file_amount = LEAD_IN_GAP + block_size;
+        assert_eq!(disk.data[file_amount], GAP_END);
+        assert_eq!(disk.data[file_amount + 1], BLOCK_FILE_AMOUNT);
+        let file_data =
+            file_amount + 2 * (1 + CRC_SIZE + BLOCK_GAP) + FILE_AMOUNT_SIZE + FILE_HEADER_SIZE;
+        assert_eq!(disk.data[file_data], GAP_END);
+        assert_eq!(disk.data[file_data + 1..file_data + 5], FILE_DATA);
+    }
+
+    #[test]
+    fn crc_checks_out() {
+        let crc = block_crc(&FILE_DATA);
+        let mut result = 0x8000;
+        for &value in FILE_DATA.iter().chain(crc.to_le_bytes().iter()) {
+            result = update_crc(result, value);
+        }
+        assert_eq!(result, 0);
+    }
+
+    #[test]
+    fn accepts_fds_header() {
+        let mut data = FDS_MAGIC.to_vec();
+        data.resize(FDS_HEADER_SIZE, 0);
+        data.extend_from_slice(&side());
+        data.extend_from_slice(&side());
+        assert!(is_fds(&data));
+        assert_eq!(DiskImage::new(&data).unwrap().num_sides, 2);
+    }
+
+    #[test]
+    fn writes_back_file() {
+        let mut file = FDS_MAGIC.to_vec();
+        file.resize(FDS_HEADER_SIZE, 0);
+        file.extend_from_slice(&side());
+        let mut disk = DiskImage::new(&file).unwrap();
+        let mut written = file.clone();
+        disk.write_file(&mut written);
+        assert!(written == file);
+
+        let file_data = disk.data.len()
+            - disk
+                .data
+                .iter()
+                .rev()
+                .position(|&value| value == 0x33)
+                .unwrap()
+            - 1;
+        disk.data[file_data] = 0x44;
+        disk.write_file(&mut written);
+        let offset = FDS_HEADER_SIZE + DISK_INFO_SIZE + FILE_AMOUNT_SIZE + FILE_HEADER_SIZE + 3;
+        assert_eq!(written[offset], 0x44);
+        written[offset] = 0x33;
+        assert!(written == file);
+    }
+
+    #[test]
+    fn writes_back_qd_crcs() {
+        let mut file = vec![];
+        for block in blocks() {
+            file.extend_from_slice(&block);
+            file.extend_from_slice(&block_crc(&block).to_le_bytes());
+        }
+        file.resize(QD_SIDE_SIZE, 0);
+        let disk = DiskImage::new(&file).unwrap();
+        assert_eq!(disk.side_size, QD_SIDE_SIZE);
+        let mut written = vec![0; file.len()];
+        disk.write_file(&mut written);
+        assert!(written == file);
+    }
+
+    #[test]
+    fn rejects_bad_images() {
+        assert!(DiskImage::new(&[]).is_err());
+        let mut data = side();
+        data[1] = 0;
+        assert!(DiskImage::new(&data).is_err());
+    }
+}
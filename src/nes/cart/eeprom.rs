@@ -0,0 +1,331 @@
+#[derive(Clone, Copy, PartialEq)]
+pub enum EepromChip {
+    X24C01,
+    C24C02,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EepromMode {
+    Idle,
+    Address,
+    WordAddress,
+    Write,
+    Read,
+}
+
+const DEVICE_TYPE_MASK: u8 = 0xF0;
+const DEVICE_TYPE: u8 = 0xA0;
+const READ_BIT_24C02: u8 = 0x01;
+const READ_BIT_24C01: u8 = 0x80;
+const ADDRESS_MASK_24C01: u8 = 0x7F;
+const ACK_BIT: u8 = 8;
+const BYTE_DONE: u8 = 9;
+
+/// Serial EEPROM on an I2C-style two wire bus, driven one SCL/SDA transition at
+/// a time by whatever board register the game bit-bangs. The 24C02 uses the
+/// standard device address byte followed by a word address, MSB first. The
+/// older X24C01 takes a 7-bit word address straight after the start condition
+/// and shifts everything LSB first.
+pub struct Eeprom {
+    pub chip: EepromChip,
+    pub data: Vec<u8>,
+    pub mode: EepromMode,
+    pub next_mode: EepromMode,
+    pub scl: bool,
+    pub sda: bool,
+    pub output: bool,
+    pub bit: u8,
+    pub shift: u8,
+    pub address: u8,
+    pub is_dirty: bool,
+}
+
+impl Eeprom {
+    pub fn new(chip: EepromChip) -> Self {
+        let size = match chip {
+            EepromChip::X24C01 => 0x80,
+            EepromChip::C24C02 => 0x100,
+        };
+        return Self {
+            chip: chip,
+            data: vec![0; size],
+            mode: EepromMode::Idle,
+            next_mode: EepromMode::Idle,
+            scl: false,
+            sda: false,
+            output: true,
+            bit: 0,
+            shift: 0,
+            address: 0,
+            is_dirty: false,
+        };
+    }
+
+    pub fn read(&self) -> bool {
+        return self.output;
+    }
+
+    pub fn write(&mut self, scl: bool, sda: bool) {
+        if self.scl && scl {
+            if self.sda && !sda {
+                self.start();
+            } else if !self.sda && sda {
+                self.stop();
+            }
+        } else if !self.scl && scl {
+            self.clock_rise(sda);
+        } else if self.scl && !scl {
+            self.clock_fall();
+        }
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    fn start(&mut self) {
+        self.mode = EepromMode::Address;
+        self.bit = 0;
+        self.shift = 0;
+        self.output = true;
+    }
+
+    fn stop(&mut self) {
+        self.mode = EepromMode::Idle;
+        self.output = true;
+    }
+
+    fn is_lsb_first(&self) -> bool {
+        return self.chip == EepromChip::X24C01;
+    }
+
+    fn clock_rise(&mut self, sda: bool) {
+        match self.mode {
+            EepromMode::Idle => {}
+            EepromMode::Read if self.bit == ACK_BIT => {
+                let is_ack = !sda;
+                self.next_mode = if is_ack {
+                    EepromMode::Read
+                } else {
+                    EepromMode::Idle
+                };
+                self.bit += 1;
+            }
+            _ if self.bit < ACK_BIT => {
+                let bit = sda as u8;
+                self.shift = if self.is_lsb_first() {
+                    (self.shift >> 1) | (bit << 7)
+                } else {
+                    (self.shift << 1) | bit
+                };
+                self.bit += 1;
+            }
+            _ => self.bit += 1,
+        }
+    }
+
+    fn clock_fall(&mut self) {
+        match self.mode {
+            EepromMode::Idle => {}
+            EepromMode::Read => self.read_clock_fall(),
+            _ if self.bit == ACK_BIT => {
+                self.receive_byte();
+            }
+            _ if self.bit == BYTE_DONE => {
+                self.output = true;
+                self.bit = 0;
+                self.shift = 0;
+                self.mode = self.next_mode;
+                if self.mode == EepromMode::Read {
+                    self.output_bit();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_clock_fall(&mut self) {
+        if self.bit < ACK_BIT {
+            self.output_bit();
+        } else if self.bit == ACK_BIT {
+            self.output = true;
+        } else {
+            self.bit = 0;
+            self.mode = self.next_mode;
+            self.address = self.wrap_address(self.address as usize + 1);
+            if self.mode == EepromMode::Read {
+                self.output_bit();
+            }
+        }
+    }
+
+    fn output_bit(&mut self) {
+        let value = self.data[self.address as usize];
+        let shift = if self.is_lsb_first() {
+            self.bit
+        } else {
+            7 - self.bit
+        };
+        self.output = (value >> shift) & 1 != 0;
+    }
+
+    fn receive_byte(&mut self) {
+        let value = self.shift;
+        let is_ack = match (self.mode, self.chip) {
+            (EepromMode::Address, EepromChip::C24C02) => {
+                let is_selected = value & DEVICE_TYPE_MASK == DEVICE_TYPE;
+                self.next_mode = if !is_selected {
+                    EepromMode::Idle
+                } else if value & READ_BIT_24C02 != 0 {
+                    EepromMode::Read
+                } else {
+                    EepromMode::WordAddress
+                };
+                is_selected
+            }
+            (EepromMode::Address, EepromChip::X24C01) => {
+                self.address = value & ADDRESS_MASK_24C01;
+                self.next_mode = if value & READ_BIT_24C01 != 0 {
+                    EepromMode::Read
+                } else {
+                    EepromMode::Write
+                };
+                true
+            }
+            (EepromMode::WordAddress, _) => {
+                self.address = self.wrap_address(value as usize);
+                self.next_mode = EepromMode::Write;
+                true
+            }
+            (EepromMode::Write, _) => {
+                self.data[self.address as usize] = value;
+                self.is_dirty = true;
+                let page_mask = self.page_size() - 1;
+                let next = (self.address + 1) & page_mask;
+                self.address = (self.address & !page_mask) | next;
+                self.next_mode = EepromMode::Write;
+                true
+            }
+            _ => false,
+        };
+        self.output = !is_ack;
+    }
+
+    fn page_size(&self) -> u8 {
+        return match self.chip {
+            EepromChip::X24C01 => 4,
+            EepromChip::C24C02 => 8,
+        };
+    }
+
+    fn wrap_address(&self, address: usize) -> u8 {
+        return (address % self.data.len()) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(eeprom: &mut Eeprom) {
+        eeprom.write(false, true);
+        eeprom.write(true, true);
+        eeprom.write(true, false);
+        eeprom.write(false, false);
+    }
+
+    fn stop(eeprom: &mut Eeprom) {
+        eeprom.write(false, false);
+        eeprom.write(true, false);
+        eeprom.write(true, true);
+    }
+
+    fn clock_bit(eeprom: &mut Eeprom, sda: bool) -> bool {
+        eeprom.write(false, sda);
+        eeprom.write(true, sda);
+        let result = eeprom.read();
+        eeprom.write(false, sda);
+        return result;
+    }
+
+    /// Sends a byte and returns whether the EEPROM acknowledged it.
+    fn send_byte(eeprom: &mut Eeprom, value: u8) -> bool {
+        for i in 0..8 {
+            let shift = if eeprom.is_lsb_first() { i } else { 7 - i };
+            clock_bit(eeprom, (value >> shift) & 1 != 0);
+        }
+        return !clock_bit(eeprom, true);
+    }
+
+    fn receive_byte(eeprom: &mut Eeprom, is_ack: bool) -> u8 {
+        let mut result = 0;
+        for i in 0..8 {
+            let bit = clock_bit(eeprom, true) as u8;
+            let shift = if eeprom.is_lsb_first() { i } else { 7 - i };
+            result |= bit << shift;
+        }
+        clock_bit(eeprom, !is_ack);
+        return result;
+    }
+
+    #[test]
+    fn c24c02_write_and_read_back() {
+        let mut eeprom = Eeprom::new(EepromChip::C24C02);
+        start(&mut eeprom);
+        assert!(send_byte(&mut eeprom, 0xA0));
+        assert!(send_byte(&mut eeprom, 0x10));
+        assert!(send_byte(&mut eeprom, 0x42));
+        assert!(send_byte(&mut eeprom, 0x43));
+        stop(&mut eeprom);
+        assert_eq!(eeprom.data[0x10], 0x42);
+        assert_eq!(eeprom.data[0x11], 0x43);
+        assert!(eeprom.is_dirty);
+
+        // Set the word address, then a repeated start switches to reading.
+        start(&mut eeprom);
+        assert!(send_byte(&mut eeprom, 0xA0));
+        assert!(send_byte(&mut eeprom, 0x10));
+        start(&mut eeprom);
+        assert!(send_byte(&mut eeprom, 0xA1));
+        assert_eq!(receive_byte(&mut eeprom, true), 0x42);
+        assert_eq!(receive_byte(&mut eeprom, false), 0x43);
+        stop(&mut eeprom);
+        assert!(eeprom.mode == EepromMode::Idle);
+    }
+
+    #[test]
+    fn c24c02_ignores_other_devices() {
+        let mut eeprom = Eeprom::new(EepromChip::C24C02);
+        start(&mut eeprom);
+        assert!(!send_byte(&mut eeprom, 0x50));
+        assert!(eeprom.mode == EepromMode::Idle);
+    }
+
+    #[test]
+    fn c24c02_write_wraps_within_page() {
+        let mut eeprom = Eeprom::new(EepromChip::C24C02);
+        start(&mut eeprom);
+        send_byte(&mut eeprom, 0xA0);
+        send_byte(&mut eeprom, 0x07);
+        send_byte(&mut eeprom, 0x11);
+        send_byte(&mut eeprom, 0x22);
+        stop(&mut eeprom);
+        assert_eq!(eeprom.data[0x07], 0x11);
+        assert_eq!(eeprom.data[0x00], 0x22);
+        assert_eq!(eeprom.data[0x08], 0x00);
+    }
+
+    #[test]
+    fn x24c01_write_and_read_back() {
+        let mut eeprom = Eeprom::new(EepromChip::X24C01);
+        start(&mut eeprom);
+        assert!(send_byte(&mut eeprom, 0x05));
+        assert!(send_byte(&mut eeprom, 0x42));
+        stop(&mut eeprom);
+        assert_eq!(eeprom.data[0x05], 0x42);
+
+        start(&mut eeprom);
+        assert!(send_byte(&mut eeprom, 0x05 | READ_BIT_24C01));
+        assert_eq!(receive_byte(&mut eeprom, false), 0x42);
+        stop(&mut eeprom);
+        assert!(eeprom.mode == EepromMode::Idle);
+    }
+}
@@ -0,0 +1,181 @@
+const COMMAND_ADDRESS_MASK: usize = 0x7FFF;
+const UNLOCK_ADDRESS_1: usize = 0x5555;
+const UNLOCK_ADDRESS_2: usize = 0x2AAA;
+const UNLOCK_VALUE_1: u8 = 0xAA;
+const UNLOCK_VALUE_2: u8 = 0x55;
+
+const COMMAND_PROGRAM: u8 = 0xA0;
+const COMMAND_ERASE: u8 = 0x80;
+const COMMAND_SOFTWARE_ID: u8 = 0x90;
+const COMMAND_RESET: u8 = 0xF0;
+const COMMAND_CHIP_ERASE: u8 = 0x10;
+const COMMAND_SECTOR_ERASE: u8 = 0x30;
+
+const SECTOR_SIZE: usize = 0x1000;
+const ERASED: u8 = 0xFF;
+const MANUFACTURER_ID: u8 = 0xBF;
+const DEVICE_ID: u8 = 0xB7;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FlashState {
+    Read,
+    Unlock1,
+    Unlock2,
+    Program,
+    Erase,
+    EraseUnlock1,
+    EraseUnlock2,
+    SoftwareId,
+}
+
+/// SST39SF040-style NOR flash as used for PRG on self-flashing homebrew
+/// boards. Games unlock it with the JEDEC command sequence at flash addresses
+/// $5555/$2AAA and then program single bytes or erase 4 KiB sectors.
+pub struct Flash {
+    pub state: FlashState,
+    pub is_dirty: bool,
+}
+
+impl Default for Flash {
+    fn default() -> Self {
+        Self {
+            state: FlashState::Read,
+            is_dirty: false,
+        }
+    }
+}
+
+impl Flash {
+    pub fn read(&self, memory: &[u8], address: usize) -> u8 {
+        if self.state == FlashState::SoftwareId {
+            return if address & 1 == 0 {
+                MANUFACTURER_ID
+            } else {
+                DEVICE_ID
+            };
+        }
+        return memory[address % memory.len()];
+    }
+
+    pub fn write(&mut self, memory: &mut [u8], address: usize, value: u8) {
+        let command_address = address & COMMAND_ADDRESS_MASK;
+        let is_unlock_1 = command_address == UNLOCK_ADDRESS_1 && value == UNLOCK_VALUE_1;
+        let is_unlock_2 = command_address == UNLOCK_ADDRESS_2 && value == UNLOCK_VALUE_2;
+        let is_command = command_address == UNLOCK_ADDRESS_1;
+        self.state = match self.state {
+            FlashState::Read if is_unlock_1 => FlashState::Unlock1,
+            FlashState::Unlock1 if is_unlock_2 => FlashState::Unlock2,
+            FlashState::Unlock2 if is_command && value == COMMAND_PROGRAM => FlashState::Program,
+            FlashState::Unlock2 if is_command && value == COMMAND_ERASE => FlashState::Erase,
+            FlashState::Unlock2 if is_command && value == COMMAND_SOFTWARE_ID => {
+                FlashState::SoftwareId
+            }
+            FlashState::Program => {
+                let index = address % memory.len();
+                memory[index] &= value;
+                self.is_dirty = true;
+                FlashState::Read
+            }
+            FlashState::Erase if is_unlock_1 => FlashState::EraseUnlock1,
+            FlashState::EraseUnlock1 if is_unlock_2 => FlashState::EraseUnlock2,
+            FlashState::EraseUnlock2 => {
+                if is_command && value == COMMAND_CHIP_ERASE {
+                    memory.fill(ERASED);
+                    self.is_dirty = true;
+                } else if value == COMMAND_SECTOR_ERASE {
+                    let begin = (address % memory.len()) & !(SECTOR_SIZE - 1);
+                    memory[begin..begin + SECTOR_SIZE].fill(ERASED);
+                    self.is_dirty = true;
+                }
+                FlashState::Read
+            }
+            FlashState::SoftwareId if value != COMMAND_RESET => FlashState::SoftwareId,
+            _ => FlashState::Read,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMORY_SIZE: usize = 0x8000;
+
+    fn command(flash: &mut Flash, memory: &mut [u8], command: u8) {
+        flash.write(memory, UNLOCK_ADDRESS_1, UNLOCK_VALUE_1);
+        flash.write(memory, UNLOCK_ADDRESS_2, UNLOCK_VALUE_2);
+        flash.write(memory, UNLOCK_ADDRESS_1, command);
+    }
+
+    #[test]
+    fn program_clears_bits() {
+        let mut flash = Flash::default();
+        let mut memory = vec![ERASED; MEMORY_SIZE];
+        command(&mut flash, &mut memory, COMMAND_PROGRAM);
+        assert!(flash.state == FlashState::Program);
+        flash.write(&mut memory, 0x1234, 0x5A);
+        assert_eq!(memory[0x1234], 0x5A);
+        assert!(flash.state == FlashState::Read);
+        assert!(flash.is_dirty);
+
+        // Programming can only clear bits, setting them takes an erase.
+        command(&mut flash, &mut memory, COMMAND_PROGRAM);
+        flash.write(&mut memory, 0x1234, 0xF0);
+        assert_eq!(memory[0x1234], 0x50);
+    }
+
+    #[test]
+    fn write_without_unlock_is_ignored() {
+        let mut flash = Flash::default();
+        let mut memory = vec![ERASED; MEMORY_SIZE];
+        flash.write(&mut memory, 0x1234, 0x00);
+        assert_eq!(memory[0x1234], ERASED);
+        assert!(!flash.is_dirty);
+
+        // A wrong second unlock byte drops back to reading.
+        flash.write(&mut memory, UNLOCK_ADDRESS_1, UNLOCK_VALUE_1);
+        flash.write(&mut memory, UNLOCK_ADDRESS_2, 0x00);
+        assert!(flash.state == FlashState::Read);
+        flash.write(&mut memory, 0x1234, 0x00);
+        assert_eq!(memory[0x1234], ERASED);
+    }
+
+    #[test]
+    fn sector_erase() {
+        let mut flash = Flash::default();
+        let mut memory = vec![0; MEMORY_SIZE];
+        command(&mut flash, &mut memory, COMMAND_ERASE);
+        flash.write(&mut memory, UNLOCK_ADDRESS_1, UNLOCK_VALUE_1);
+        flash.write(&mut memory, UNLOCK_ADDRESS_2, UNLOCK_VALUE_2);
+        flash.write(&mut memory, 0x2345, COMMAND_SECTOR_ERASE);
+        assert!(memory[0x2000..0x3000].iter().all(|&value| value == ERASED));
+        assert_eq!(memory[0x1FFF], 0);
+        assert_eq!(memory[0x3000], 0);
+        assert!(flash.state == FlashState::Read);
+    }
+
+    #[test]
+    fn chip_erase() {
+        let mut flash = Flash::default();
+        let mut memory = vec![0; MEMORY_SIZE];
+        command(&mut flash, &mut memory, COMMAND_ERASE);
+        command(&mut flash, &mut memory, COMMAND_CHIP_ERASE);
+        assert!(memory.iter().all(|&value| value == ERASED));
+        assert!(flash.is_dirty);
+    }
+
+    #[test]
+    fn software_id() {
+        let mut flash = Flash::default();
+        let mut memory = vec![0; MEMORY_SIZE];
+        command(&mut flash, &mut memory, COMMAND_SOFTWARE_ID);
+        assert_eq!(flash.read(&memory, 0), MANUFACTURER_ID);
+        assert_eq!(flash.read(&memory, 1), DEVICE_ID);
+
+        // Stays in ID mode until a reset command.
+        flash.write(&mut memory, 0x1234, 0x00);
+        assert_eq!(flash.read(&memory, 0), MANUFACTURER_ID);
+        flash.write(&mut memory, 0, COMMAND_RESET);
+        assert_eq!(flash.read(&memory, 0), 0);
+    }
+}
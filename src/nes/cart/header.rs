@@ -1,13 +1,29 @@
 use bitmask_enum::bitmask;
 
+use crate::nes::ppu::VramMirror;
+
+use super::load_error::LoadError;
+
+pub const HEADER_SIZE: usize = 0x10;
 const FORMAT_TAG_SIZE: usize = 4;
 const ROM_BANK_SIZE: usize = 0x4000;
 
 const PRG_BANKS_ADDRESS: usize = 4;
 const CHR_BANKS_ADDRESS: usize = 5;
-const CONTROL_1: usize = 7;
-const CONTROL_2: usize = 8;
-const RAM_BANK_ADDRESS: usize = 9;
+const CONTROL_1: usize = 6;
+const CONTROL_2: usize = 7;
+const RAM_BANK_ADDRESS: usize = 8;
+const RAM_SHIFT_ADDRESS: usize = 10;
+const CHR_RAM_SHIFT_ADDRESS: usize = 11;
+const TIMING_ADDRESS: usize = 12;
+const SYSTEM_TYPE_ADDRESS: usize = 13;
+
+const NES2_FORMAT: u8 = 0b00001000;
+const PRG_RAM_BANK_SIZE: usize = 0x2000;
+const CHR_RAM_DEFAULT_SIZE: usize = 0x2000;
+const TIMING_MASK: u8 = 0x03;
+const CHR_BANK_SIZE: usize = 0x2000;
+const TRAINER_SIZE: usize = 512;
 
 #[derive(Debug)]
 pub struct Header {
@@ -16,6 +32,12 @@ pub struct Header {
     pub num_chr_banks: u8,
     pub control: Control,
     pub num_ram_banks: u8,
+    pub ram_shifts: u8,
+    pub chr_ram_shifts: u8,
+    pub timing: u8,
+    pub system_type: u8,
+    /// Single-screen mirroring, which iNES can't express but UNIF can.
+    pub single_screen: Option<VramMirror>,
 }
 
 impl Default for Header {
@@ -26,6 +48,11 @@ impl Default for Header {
             num_chr_banks: Default::default(),
             control: Default::default(),
             num_ram_banks: Default::default(),
+            ram_shifts: Default::default(),
+            chr_ram_shifts: Default::default(),
+            timing: Default::default(),
+            system_type: Default::default(),
+            single_screen: None,
         }
     }
 }
@@ -38,7 +65,225 @@ impl Header {
             num_chr_banks: ines_data[CHR_BANKS_ADDRESS],
             control: Control::new(ines_data[CONTROL_1], ines_data[CONTROL_2]),
             num_ram_banks: ines_data[RAM_BANK_ADDRESS],
+            ram_shifts: ines_data[RAM_SHIFT_ADDRESS],
+            chr_ram_shifts: ines_data[CHR_RAM_SHIFT_ADDRESS],
+            timing: ines_data[TIMING_ADDRESS],
+            system_type: ines_data[SYSTEM_TYPE_ADDRESS],
+            single_screen: None,
+        };
+    }
+
+    /// Reads the header of an iNES file, checking the tag and that the file
+    /// holds all the trainer and ROM data the header promises.
+    pub fn from_ines(raw_data: &[u8]) -> Result<Self, LoadError> {
+        if raw_data.len() < HEADER_SIZE {
+            return Err(LoadError::InvalidFormat("file too short".to_string()));
+        }
+        let result = Self::new(&raw_data.to_vec());
+        if !result.is_valid_tag() {
+            return Err(LoadError::InvalidFormat("missing iNES header".to_string()));
+        }
+        if raw_data.len() < result.rom_end() {
+            let reason = format!(
+                "header promises {} bytes, file has {}",
+                result.rom_end(),
+                raw_data.len()
+            );
+            return Err(LoadError::InvalidFormat(reason));
+        }
+        return Ok(result);
+    }
+
+    pub fn is_nes2(&self) -> bool {
+        let format = self.control.control_two & ControlTwo::InesFormat;
+        return format.bits() == NES2_FORMAT;
+    }
+
+    pub fn has_battery(&self) -> bool {
+        return self.control.control_one.contains(ControlOne::Ram);
+    }
+
+    pub fn submapper(&self) -> u8 {
+        if !self.is_nes2() {
+            return 0;
+        }
+        return self.num_ram_banks >> 4;
+    }
+
+    pub fn region(&self) -> Region {
+        if !self.is_nes2() {
+            return Region::Ntsc;
+        }
+        let result = match self.timing & TIMING_MASK {
+            0 => Region::Ntsc,
+            1 => Region::Pal,
+            2 => Region::Multi,
+            _ => Region::Dendy,
+        };
+        return result;
+    }
+
+    pub fn console_type(&self) -> ConsoleType {
+        let console = self.control.control_two & ControlTwo::ConsoleType;
+        let result = match console.bits() {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::PlayChoice10,
+            _ => ConsoleType::Extended,
         };
+        return result;
+    }
+
+    /// Which RGB PPU a Vs. System game was made for, as numbered in NES 2.0
+    /// byte 13. iNES headers can't say, so they get the plain 2C03.
+    pub fn vs_ppu_type(&self) -> u8 {
+        if !self.is_nes2() {
+            return 0;
+        }
+        return self.system_type & 0x0F;
+    }
+
+    pub fn prg_rom_size(&self) -> usize {
+        return self.num_prg_banks as usize * ROM_BANK_SIZE;
+    }
+
+    pub fn chr_rom_size(&self) -> usize {
+        return self.num_chr_banks as usize * CHR_BANK_SIZE;
+    }
+
+    pub fn trainer_size(&self) -> usize {
+        return if self.has_trainer() { TRAINER_SIZE } else { 0 };
+    }
+
+    /// Where PRG-ROM starts in an iNES file, after the header and trainer.
+    pub fn prg_rom_begin(&self) -> usize {
+        return HEADER_SIZE + self.trainer_size();
+    }
+
+    pub fn chr_rom_begin(&self) -> usize {
+        return self.prg_rom_begin() + self.prg_rom_size();
+    }
+
+    /// The end of the ROM data in an iNES file. Anything after it is extra.
+    pub fn rom_end(&self) -> usize {
+        return self.chr_rom_begin() + self.chr_rom_size();
+    }
+
+    pub fn set_mapper(&mut self, mapper: u8, submapper: u8) {
+        let control = &mut self.control;
+        let lower = ControlOne::from((mapper & 0x0F) << 4);
+        let upper = ControlTwo::from(mapper & 0xF0);
+        control.control_one = (control.control_one & !ControlOne::Mapper) | lower;
+        control.control_two = (control.control_two & !ControlTwo::Mapper) | upper;
+        if self.is_nes2() {
+            self.num_ram_banks = (self.num_ram_banks & 0x0F) | (submapper << 4);
+        }
+    }
+
+    pub fn set_mirroring(&mut self, mirroring: VramMirror) {
+        let control = &mut self.control.control_one;
+        *control &= !(ControlOne::VramLayout | ControlOne::Mirroring);
+        self.single_screen = None;
+        match mirroring {
+            VramMirror::FourScreen => *control |= ControlOne::VramLayout,
+            VramMirror::Vertical => *control |= ControlOne::Mirroring,
+            VramMirror::SingleScreenA | VramMirror::SingleScreenB => {
+                self.single_screen = Some(mirroring)
+            }
+            VramMirror::Horizontal => {}
+        }
+    }
+
+    pub fn set_battery(&mut self, has_battery: bool) {
+        let control = &mut self.control.control_one;
+        if has_battery {
+            *control |= ControlOne::Ram;
+        } else {
+            *control &= !ControlOne::Ram;
+        }
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.make_nes2();
+        self.timing = match region {
+            Region::Ntsc => 0,
+            Region::Pal => 1,
+            Region::Multi => 2,
+            Region::Dendy => 3,
+        };
+    }
+
+    pub fn set_ram_sizes(&mut self, prg_ram_size: usize, chr_ram_size: usize) {
+        self.make_nes2();
+        self.store_ram_sizes(prg_ram_size, chr_ram_size);
+    }
+
+    /// iNES can't express exact RAM sizes or timing, so corrected headers are
+    /// switched over to NES 2.0 with the sizes they already implied.
+    fn make_nes2(&mut self) {
+        if self.is_nes2() {
+            return;
+        }
+        let prg_ram_size = self.prg_ram_size();
+        let chr_ram_size = self.chr_ram_size();
+        let format = ControlTwo::from(NES2_FORMAT);
+        self.control.control_two = (self.control.control_two & !ControlTwo::InesFormat) | format;
+        self.num_ram_banks = 0;
+        self.timing = 0;
+        self.system_type = 0;
+        self.store_ram_sizes(prg_ram_size, chr_ram_size);
+    }
+
+    fn store_ram_sizes(&mut self, prg_ram_size: usize, chr_ram_size: usize) {
+        let prg_shift = size_shift(prg_ram_size);
+        self.ram_shifts = if self.has_battery() {
+            prg_shift << 4
+        } else {
+            prg_shift
+        };
+        self.chr_ram_shifts = size_shift(chr_ram_size);
+    }
+
+    pub fn mirroring(&self) -> VramMirror {
+        if let Some(single_screen) = self.single_screen {
+            return single_screen;
+        }
+        let control = self.control.control_one;
+        if control.contains(ControlOne::VramLayout) {
+            return VramMirror::FourScreen;
+        }
+        if control.contains(ControlOne::Mirroring) {
+            return VramMirror::Vertical;
+        }
+        return VramMirror::Horizontal;
+    }
+
+    pub fn has_trainer(&self) -> bool {
+        return self.control.control_one.contains(ControlOne::Trainer);
+    }
+
+    pub fn prg_ram_size(&self) -> usize {
+        if self.is_nes2() {
+            let volatile = shift_size(self.ram_shifts & 0x0F);
+            let non_volatile = shift_size(self.ram_shifts >> 4);
+            return volatile + non_volatile;
+        }
+        return (self.num_ram_banks as usize).max(1) * PRG_RAM_BANK_SIZE;
+    }
+
+    /// iNES has no field for CHR-RAM, so boards without CHR-ROM are assumed to
+    /// carry the usual 8 KiB. NES 2.0 spells out the size, which also covers
+    /// boards with both ROM and RAM.
+    pub fn chr_ram_size(&self) -> usize {
+        if self.is_nes2() {
+            let volatile = shift_size(self.chr_ram_shifts & 0x0F);
+            let non_volatile = shift_size(self.chr_ram_shifts >> 4);
+            return volatile + non_volatile;
+        }
+        if self.num_chr_banks == 0 {
+            return CHR_RAM_DEFAULT_SIZE;
+        }
+        return 0;
     }
 
     pub fn is_valid_tag(&self) -> bool {
@@ -47,6 +292,34 @@ impl Header {
     }
 }
 
+fn shift_size(shift: u8) -> usize {
+    return if shift == 0 { 0 } else { 64 << shift };
+}
+
+fn size_shift(size: usize) -> u8 {
+    return if size == 0 {
+        0
+    } else {
+        (size / 64).trailing_zeros() as u8
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    Multi,
+    Dendy,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    PlayChoice10,
+    Extended,
+}
+
 #[derive(Debug)]
 pub struct Control {
     pub control_one: ControlOne,
@@ -73,7 +346,7 @@ impl Control {
     pub fn get_mapper(&self) -> u8 {
         let upper = self.control_two & ControlTwo::Mapper;
         let lower = self.control_one & ControlOne::Mapper;
-        let result = (upper.bits()) & (lower.bits() >> 4);
+        let result = (upper.bits()) | (lower.bits() >> 4);
         return result;
     }
 }
@@ -90,4 +363,30 @@ pub enum ControlOne {
 pub enum ControlTwo {
     Mapper = 0xF0,
     InesFormat = 0b00001100,
+    ConsoleType = 0b00000011,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ines(num_prg_banks: u8, num_chr_banks: u8, length: usize) -> Vec<u8> {
+        let mut result = vec![0x4E, 0x45, 0x53, 0x1A, num_prg_banks, num_chr_banks];
+        result.resize(length, 0);
+        return result;
+    }
+
+    #[test]
+    fn from_ines_checks_file() {
+        let complete = ines(2, 1, HEADER_SIZE + 2 * ROM_BANK_SIZE + CHR_BANK_SIZE);
+        let header = Header::from_ines(&complete).unwrap();
+        assert_eq!(header.chr_rom_begin(), HEADER_SIZE + 2 * ROM_BANK_SIZE);
+        assert_eq!(header.rom_end(), complete.len());
+
+        assert!(Header::from_ines(&complete[..complete.len() - 1]).is_err());
+        assert!(Header::from_ines(&complete[..HEADER_SIZE - 1]).is_err());
+        let mut bad_tag = complete.clone();
+        bad_tag[3] = 0;
+        assert!(Header::from_ines(&bad_tag).is_err());
+    }
 }
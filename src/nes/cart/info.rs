@@ -0,0 +1,270 @@
+use std::fmt;
+
+use crate::nes::ppu::PpuModel;
+
+use super::{
+    database::{self, RomHashes},
+    disk::{self, DiskImage},
+    header::{ConsoleType, Header, Region},
+    load_error::LoadError,
+    mapper::{self, nsf::NsfMapper},
+    nsf::{self, Nsf},
+    unif::{self, Unif},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RomFormat {
+    Ines,
+    Nes2,
+    Unif,
+    Fds,
+    Nsf,
+    Nsfe,
+}
+
+enum Value {
+    Text(String),
+    Number(usize),
+    Flag(bool),
+}
+
+/// What can be learned about a ROM file without running it. Parsing stops
+/// short of building a mapper, so boards we don't emulate can still be
+/// inspected.
+pub struct RomInfo {
+    pub format: RomFormat,
+    pub header: Header,
+    pub title: Option<String>,
+    pub board: Option<String>,
+    pub region: Region,
+    pub num_disk_sides: usize,
+    pub num_tracks: usize,
+    pub hashes: RomHashes,
+    /// Patch files applied before hashing, so the hashes are of the
+    /// patched ROM rather than the file on disk.
+    pub patches: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Default for RomInfo {
+    fn default() -> Self {
+        Self {
+            format: RomFormat::Ines,
+            header: Default::default(),
+            title: None,
+            board: None,
+            region: Region::Ntsc,
+            num_disk_sides: 0,
+            num_tracks: 0,
+            hashes: Default::default(),
+            patches: vec![],
+            warnings: vec![],
+        }
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, value) in self.fields() {
+            match value {
+                Value::Text(text) => writeln!(f, "{:<14} {}", key, text)?,
+                Value::Number(number) => writeln!(f, "{:<14} {}", key, number)?,
+                Value::Flag(flag) => {
+                    writeln!(f, "{:<14} {}", key, if flag { "yes" } else { "no" })?
+                }
+            }
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        return Ok(());
+    }
+}
+
+impl RomInfo {
+    pub fn new(raw_data: &[u8]) -> Result<Self, LoadError> {
+        if unif::is_unif(raw_data) {
+            return Self::from_unif(raw_data);
+        }
+        if nsf::is_nsf(raw_data) {
+            return Self::from_nsf(raw_data);
+        }
+        if disk::is_fds(raw_data) {
+            return Self::from_fds(raw_data);
+        }
+        return Self::from_ines(raw_data);
+    }
+
+    fn from_ines(raw_data: &[u8]) -> Result<Self, LoadError> {
+        let header = Header::from_ines(raw_data)?;
+        let prg_begin = header.prg_rom_begin();
+        let prg_end = header.chr_rom_begin();
+        let chr_end = header.rom_end();
+        let format = if header.is_nes2() {
+            RomFormat::Nes2
+        } else {
+            RomFormat::Ines
+        };
+        let hashes = RomHashes::new(&raw_data[prg_begin..prg_end], &raw_data[prg_end..chr_end]);
+        let mut result = Self::from_header(format, header, hashes);
+        if raw_data.len() > chr_end {
+            let extra = raw_data.len() - chr_end;
+            let warning = if result.header.console_type() == ConsoleType::PlayChoice10 {
+                format!("{} bytes of PlayChoice-10 INST ROM ignored", extra)
+            } else {
+                format!("{} bytes of trailing data ignored", extra)
+            };
+            result.warnings.push(warning);
+        }
+        return Ok(result);
+    }
+
+    fn from_unif(raw_data: &[u8]) -> Result<Self, LoadError> {
+        let unif = Unif::new(raw_data)?;
+        let hashes = RomHashes::new(&unif.prg_rom, &unif.chr_rom);
+        let mut result = Self::from_header(RomFormat::Unif, unif.header, hashes);
+        result.title = result.title.or(unif.title);
+        result.board = Some(unif.board);
+        return Ok(result);
+    }
+
+    fn from_fds(raw_data: &[u8]) -> Result<Self, LoadError> {
+        let disk = DiskImage::new(raw_data)?;
+        return Ok(Self {
+            format: RomFormat::Fds,
+            num_disk_sides: disk.num_sides,
+            hashes: RomHashes::new(raw_data, &[]),
+            ..Default::default()
+        });
+    }
+
+    fn from_nsf(raw_data: &[u8]) -> Result<Self, LoadError> {
+        let nsf = Nsf::new(raw_data)?;
+        let format = if nsf::is_nsfe(raw_data) {
+            RomFormat::Nsfe
+        } else {
+            RomFormat::Nsf
+        };
+        let unsupported = NsfMapper::unsupported_expansion(&nsf);
+        let warnings = nsf::expansion_names(unsupported)
+            .iter()
+            .map(|name| format!("{} expansion audio is not supported", name))
+            .collect();
+        return Ok(Self {
+            format: format,
+            title: Some(nsf.title.clone()),
+            warnings: warnings,
+            region: nsf.region,
+            num_tracks: nsf.num_tracks,
+            hashes: RomHashes::new(&nsf.data, &[]),
+            ..Default::default()
+        });
+    }
+
+    /// Checks a cartridge header against the database the same way loading
+    /// does, so the info shown matches what would actually run.
+    fn from_header(format: RomFormat, mut header: Header, hashes: RomHashes) -> Self {
+        let mut warnings = vec![];
+        let game = database::find(&hashes);
+        if let Some(game) = game {
+            for correction in database::correct_header(&mut header, game) {
+                warnings.push(format!("header corrected: {}", correction));
+            }
+        }
+        let mapper = header.control.get_mapper();
+        if !mapper::is_supported(mapper) {
+            warnings.push(format!("mapper {} is not supported", mapper));
+        }
+        if header.has_trainer() {
+            warnings.push("has a 512 byte trainer".to_string());
+        }
+        return Self {
+            format: format,
+            region: header.region(),
+            header: header,
+            title: game.map(|game| game.title.to_string()),
+            board: game
+                .filter(|game| !game.board.is_empty())
+                .map(|game| game.board.to_string()),
+            hashes: hashes,
+            warnings: warnings,
+            ..Default::default()
+        };
+    }
+
+    /// The info as a single line of JSON, for scripts.
+    pub fn to_json(&self, filename: &str) -> String {
+        let mut fields = vec![format!("\"file\":{}", json_string(filename))];
+        for (key, value) in self.fields() {
+            let value = match value {
+                Value::Text(text) => json_string(&text),
+                Value::Number(number) => number.to_string(),
+                Value::Flag(flag) => flag.to_string(),
+            };
+            fields.push(format!("\"{}\":{}", key, value));
+        }
+        let warnings: Vec<String> = self.warnings.iter().map(|w| json_string(w)).collect();
+        fields.push(format!("\"warnings\":[{}]", warnings.join(",")));
+        return format!("{{{}}}", fields.join(","));
+    }
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        let mut result = vec![("format", Value::Text(format!("{:?}", self.format)))];
+        if let Some(title) = &self.title {
+            result.push(("title", Value::Text(title.clone())));
+        }
+        match self.format {
+            RomFormat::Ines | RomFormat::Nes2 | RomFormat::Unif => {
+                let header = &self.header;
+                let mapper = header.control.get_mapper();
+                if let Some(board) = &self.board {
+                    result.push(("board", Value::Text(board.clone())));
+                }
+                result.push(("mapper", Value::Number(mapper as usize)));
+                let name = mapper::name(mapper).unwrap_or("unknown");
+                result.push(("mapper_name", Value::Text(name.to_string())));
+                result.push(("submapper", Value::Number(header.submapper() as usize)));
+                result.push(("prg_rom_size", Value::Number(header.prg_rom_size())));
+                result.push(("chr_rom_size", Value::Number(header.chr_rom_size())));
+                result.push(("prg_ram_size", Value::Number(header.prg_ram_size())));
+                result.push(("chr_ram_size", Value::Number(header.chr_ram_size())));
+                let mirroring = format!("{:?}", header.mirroring());
+                result.push(("mirroring", Value::Text(mirroring)));
+                result.push(("battery", Value::Flag(header.has_battery())));
+                result.push(("trainer", Value::Flag(header.has_trainer())));
+                let console_type = format!("{:?}", header.console_type());
+                result.push(("console_type", Value::Text(console_type)));
+                let ppu = PpuModel::new(header).name();
+                result.push(("ppu", Value::Text(ppu)));
+            }
+            RomFormat::Fds => {
+                result.push(("disk_sides", Value::Number(self.num_disk_sides)));
+            }
+            RomFormat::Nsf | RomFormat::Nsfe => {
+                result.push(("tracks", Value::Number(self.num_tracks)));
+            }
+        }
+        result.push(("region", Value::Text(format!("{:?}", self.region))));
+        if !self.patches.is_empty() {
+            result.push(("patches", Value::Text(self.patches.join(", "))));
+        }
+        let crc32 = format!("{:08X}", self.hashes.crc32);
+        result.push(("crc32", Value::Text(crc32)));
+        result.push(("sha1", Value::Text(self.hashes.sha1.clone())));
+        return result;
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    return result;
+}
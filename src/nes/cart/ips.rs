@@ -0,0 +1,154 @@
+use super::load_error::LoadError;
+
+const MAGIC: &[u8] = b"PATCH";
+const FOOTER: &[u8] = b"EOF";
+const MAX_RECORD_SIZE: usize = 0xFFFF;
+
+pub fn is_ips(patch: &[u8]) -> bool {
+    return patch.starts_with(MAGIC);
+}
+
+/// Applies an IPS patch. Records may write past the end of the data, in which
+/// case it grows, and an optional truncation length follows the footer.
+pub fn apply(data: &[u8], patch: &[u8]) -> Result<Vec<u8>, LoadError> {
+    if !is_ips(patch) {
+        return Err(invalid("missing IPS header"));
+    }
+    let mut result = data.to_vec();
+    let mut offset = MAGIC.len();
+    loop {
+        let record = patch
+            .get(offset..offset + 3)
+            .ok_or(invalid("truncated IPS patch"))?;
+        if record == FOOTER {
+            offset += 3;
+            break;
+        }
+        let address = read_be(record);
+        let size = read_be(
+            patch
+                .get(offset + 3..offset + 5)
+                .ok_or(invalid("truncated IPS record"))?,
+        );
+        offset += 5;
+        let (bytes, length) = if size == 0 {
+            let rle = patch
+                .get(offset..offset + 3)
+                .ok_or(invalid("truncated IPS record"))?;
+            offset += 3;
+            (vec![rle[2]; read_be(&rle[0..2])], 0)
+        } else {
+            let bytes = patch
+                .get(offset..offset + size)
+                .ok_or(invalid("truncated IPS record"))?;
+            (bytes.to_vec(), size)
+        };
+        offset += length;
+        if result.len() < address + bytes.len() {
+            result.resize(address + bytes.len(), 0);
+        }
+        result[address..address + bytes.len()].copy_from_slice(&bytes);
+    }
+    if let Some(truncate) = patch.get(offset..offset + 3) {
+        result.truncate(read_be(truncate));
+    }
+    return Ok(result);
+}
+
+/// Builds an IPS patch turning `original` into `modified`, which must be the
+/// same length.
+pub fn create(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let mut result = MAGIC.to_vec();
+    let mut address = 0;
+    while address < modified.len() {
+        if original[address] == modified[address] {
+            address += 1;
+            continue;
+        }
+        let begin = address;
+        while address < modified.len()
+            && address - begin < MAX_RECORD_SIZE
+            && original[address] != modified[address]
+        {
+            address += 1;
+        }
+        result.extend_from_slice(&(begin as u32).to_be_bytes()[1..]);
+        result.extend_from_slice(&((address - begin) as u16).to_be_bytes());
+        result.extend_from_slice(&modified[begin..address]);
+    }
+    result.extend_from_slice(FOOTER);
+    return result;
+}
+
+fn read_be(bytes: &[u8]) -> usize {
+    return bytes
+        .iter()
+        .fold(0, |result, &byte| (result << 8) | byte as usize);
+}
+
+fn invalid(reason: &str) -> LoadError {
+    return LoadError::InvalidPatch(reason.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(address: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut result = address.to_be_bytes()[1..].to_vec();
+        result.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+        result.extend_from_slice(bytes);
+        return result;
+    }
+
+    fn patch(records: &[Vec<u8>], footer: &[u8]) -> Vec<u8> {
+        let mut result = MAGIC.to_vec();
+        for record in records {
+            result.extend_from_slice(record);
+        }
+        result.extend_from_slice(FOOTER);
+        result.extend_from_slice(footer);
+        return result;
+    }
+
+    #[test]
+    fn applies_records() {
+        let patch = patch(&[record(1, &[0xAA, 0xBB]), record(6, &[0xCC])], &[]);
+        let result = apply(&[0; 4], &patch).unwrap();
+        assert_eq!(result, [0, 0xAA, 0xBB, 0, 0, 0, 0xCC]);
+    }
+
+    #[test]
+    fn applies_rle_records() {
+        // A zero size is followed by a run length and the byte to repeat.
+        let rle = record(2, &[])
+            .into_iter()
+            .chain([0x00, 0x03, 0x77])
+            .collect();
+        let result = apply(&[0; 8], &patch(&[rle], &[])).unwrap();
+        assert_eq!(result, [0, 0, 0x77, 0x77, 0x77, 0, 0, 0]);
+    }
+
+    #[test]
+    fn truncates_after_footer() {
+        let result = apply(&[1; 8], &patch(&[], &[0x00, 0x00, 0x05])).unwrap();
+        assert_eq!(result, [1; 5]);
+    }
+
+    #[test]
+    fn rejects_truncated_patches() {
+        assert!(apply(&[0; 4], b"PATCH").is_err());
+        let mut short_record = MAGIC.to_vec();
+        short_record.extend_from_slice(&record(0, &[1, 2, 3])[..6]);
+        assert!(apply(&[0; 4], &short_record).is_err());
+        assert!(apply(&[0; 4], b"NOTIPS").is_err());
+    }
+
+    #[test]
+    fn create_round_trips() {
+        let original = [0, 1, 2, 3, 4, 5, 6, 7];
+        let modified = [9, 1, 2, 8, 8, 5, 6, 9];
+        let patch = create(&original, &modified);
+        assert_eq!(apply(&original, &patch).unwrap(), modified);
+    }
+}
@@ -0,0 +1,28 @@
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    InvalidFormat(String),
+    UnsupportedBoard(String),
+    MissingBios(String),
+    InvalidPatch(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "{}", error),
+            LoadError::InvalidFormat(reason) => write!(f, "invalid ROM file: {}", reason),
+            LoadError::UnsupportedBoard(board) => write!(f, "unsupported board {}", board),
+            LoadError::MissingBios(path) => write!(f, "FDS BIOS not found at {}", path),
+            LoadError::InvalidPatch(reason) => write!(f, "could not apply patch: {}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        return LoadError::Io(error);
+    }
+}
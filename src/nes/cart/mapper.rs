@@ -0,0 +1,208 @@
+mod action53;
+mod bandai_fcg;
+pub mod fds;
+mod gtrom;
+mod nrom;
+pub mod nsf;
+mod sunsoft_fme7;
+mod unrom512;
+mod vs_unisystem;
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::nes::ppu::VramMirror;
+
+use super::{chr::Chr, header::Header, load_error::LoadError, prg_ram::PrgRam};
+use action53::Action53;
+use bandai_fcg::{BandaiBoard, BandaiFcg};
+use gtrom::Gtrom;
+use nrom::Nrom;
+use sunsoft_fme7::SunsoftFme7;
+use unrom512::Unrom512;
+use vs_unisystem::VsUnisystem;
+
+pub type MapperRef = Rc<RefCell<dyn Mapper>>;
+
+pub trait Mapper {
+    fn cpu_read(&mut self, address: usize) -> u8;
+    fn cpu_write(&mut self, address: usize, value: u8);
+    fn ppu_read(&mut self, address: usize) -> u8;
+    fn ppu_write(&mut self, address: usize, value: u8);
+    /// The writable part of pattern memory, empty on CHR-ROM only boards.
+    fn chr_ram(&mut self) -> &mut [u8];
+
+    fn tick(&mut self, n_cycles: i64) {}
+
+    fn is_irq(&self) -> bool {
+        return false;
+    }
+
+    fn expansion_audio(&self) -> f32 {
+        return 0.0;
+    }
+
+    /// Mirroring selected by the mapper's own registers. Boards with mirroring
+    /// fixed by solder pads return None and the header setting is used.
+    fn mirroring(&self) -> Option<VramMirror> {
+        return None;
+    }
+
+    /// Lets a mapper supply nametable bytes itself instead of console VRAM.
+    fn nametable_read(&mut self, address: usize) -> Option<u8> {
+        return None;
+    }
+
+    fn nametable_write(&mut self, address: usize, value: u8) -> bool {
+        return false;
+    }
+
+    fn save_data(&mut self) -> Option<&mut [u8]> {
+        return None;
+    }
+
+    /// The unmodified contents of `save_data`. Mappers that have one are saved
+    /// as a diff against it instead of a full copy.
+    fn save_base(&self) -> Option<&[u8]> {
+        return None;
+    }
+
+    /// Called once `save_data` has been filled from a save file.
+    fn save_data_loaded(&mut self) {}
+
+    fn poll_save_dirty(&mut self) -> bool {
+        return false;
+    }
+
+    fn num_disk_sides(&self) -> usize {
+        return 0;
+    }
+
+    /// Ejects the current disk and, after a short delay, inserts `side`.
+    fn insert_disk(&mut self, side: Option<usize>) {}
+
+    /// Sets the NSF track INIT is called with on the next reset.
+    fn select_track(&mut self, track: usize) {}
+}
+
+pub fn new(
+    header: &Header,
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+    trainer: &[u8],
+) -> Result<MapperRef, LoadError> {
+    let mapper = header.control.get_mapper();
+    if !is_supported(mapper) {
+        return Err(LoadError::UnsupportedBoard(board_name(mapper)));
+    }
+    let mut prg_ram = PrgRam::new(header.prg_ram_size(), header.has_battery());
+    if !trainer.is_empty() {
+        prg_ram.load_trainer(trainer);
+    }
+    let chr = Chr::new(chr_rom, chr_ram_size(header, mapper));
+    let result: MapperRef = match mapper {
+        Nrom::NUMBER => Rc::new(RefCell::new(Nrom::new(prg_rom, chr, prg_ram))),
+        Action53::NUMBER => Rc::new(RefCell::new(Action53::new(prg_rom, chr))),
+        Unrom512::NUMBER => Rc::new(RefCell::new(Unrom512::new(header, prg_rom, chr))),
+        BandaiFcg::NUMBER_FCG => Rc::new(RefCell::new(BandaiFcg::new(
+            BandaiBoard::Fcg,
+            prg_rom,
+            chr,
+            prg_ram,
+        ))),
+        BandaiFcg::NUMBER_LZ93D50_SRAM => Rc::new(RefCell::new(BandaiFcg::new(
+            BandaiBoard::Lz93d50Sram,
+            prg_rom,
+            chr,
+            prg_ram,
+        ))),
+        BandaiFcg::NUMBER_LZ93D50_EEPROM => Rc::new(RefCell::new(BandaiFcg::new(
+            BandaiBoard::Lz93d50Eeprom,
+            prg_rom,
+            chr,
+            prg_ram,
+        ))),
+        SunsoftFme7::NUMBER => Rc::new(RefCell::new(SunsoftFme7::new(prg_rom, chr, prg_ram))),
+        Gtrom::NUMBER => Rc::new(RefCell::new(Gtrom::new(prg_rom, chr))),
+        VsUnisystem::NUMBER => Rc::new(RefCell::new(VsUnisystem::new(prg_rom, chr, prg_ram))),
+        _ => unreachable!(),
+    };
+    return Ok(result);
+}
+
+/// iNES 1.0 headers can't say how much CHR-RAM a board has, so boards that
+/// ship with more than the usual 8 KiB get their own default.
+fn chr_ram_size(header: &Header, mapper: u8) -> usize {
+    if header.is_nes2() || header.num_chr_banks != 0 {
+        return header.chr_ram_size();
+    }
+    return match mapper {
+        Action53::NUMBER => Action53::CHR_RAM_SIZE,
+        Unrom512::NUMBER => Unrom512::CHR_RAM_SIZE,
+        Gtrom::NUMBER => Gtrom::CHR_RAM_SIZE,
+        _ => header.chr_ram_size(),
+    };
+}
+
+pub fn empty() -> MapperRef {
+    return Rc::new(RefCell::new(Nrom::default()));
+}
+
+pub fn is_supported(number: u8) -> bool {
+    return matches!(
+        number,
+        Nrom::NUMBER
+            | Action53::NUMBER
+            | Unrom512::NUMBER
+            | BandaiFcg::NUMBER_FCG
+            | BandaiFcg::NUMBER_LZ93D50_SRAM
+            | BandaiFcg::NUMBER_LZ93D50_EEPROM
+            | SunsoftFme7::NUMBER
+            | Gtrom::NUMBER
+            | VsUnisystem::NUMBER
+    );
+}
+
+/// How an unsupported mapper is named in errors, e.g. "MMC3 (mapper 4)".
+fn board_name(number: u8) -> String {
+    return match name(number) {
+        Some(name) => format!("{} (mapper {})", name, number),
+        None => format!("mapper {}", number),
+    };
+}
+
+/// The usual name for an iNES mapper number, covering the common boards
+/// whether or not we emulate them.
+pub fn name(number: u8) -> Option<&'static str> {
+    let result = match number {
+        0 => "NROM",
+        1 => "MMC1",
+        2 => "UxROM",
+        3 => "CNROM",
+        4 => "MMC3",
+        5 => "MMC5",
+        7 => "AxROM",
+        9 => "MMC2",
+        10 => "MMC4",
+        11 => "Color Dreams",
+        13 => "CPROM",
+        16 => "Bandai FCG",
+        19 => "Namco 163",
+        21 | 23 | 25 => "VRC4",
+        22 => "VRC2",
+        24 | 26 => "VRC6",
+        28 => "Action 53",
+        30 => "UNROM 512",
+        34 => "BNROM/NINA-001",
+        66 => "GxROM",
+        69 => "Sunsoft FME-7",
+        71 => "Camerica",
+        85 => "VRC7",
+        99 => "Vs. System",
+        111 => "GTROM",
+        153 => "Bandai LZ93D50 with SRAM",
+        159 => "Bandai LZ93D50 with 24C01",
+        206 => "Namco 118",
+        _ => return None,
+    };
+    return Some(result);
+}
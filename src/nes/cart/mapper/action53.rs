@@ -0,0 +1,151 @@
+use crate::nes::cart::{banks::Banks, chr::Chr};
+use crate::nes::{memory::Memory, ppu::VramMirror};
+
+use super::Mapper;
+
+const PRG_WINDOW_SIZE: usize = 0x4000;
+const NUM_PRG_WINDOWS: usize = 2;
+const CHR_WINDOW_SIZE: usize = 0x2000;
+
+const SELECT_BEGIN: usize = 0x5000;
+const SELECT_END: usize = 0x5FFF;
+const PRG_BEGIN: usize = 0x8000;
+
+const REGISTER_CHR: u8 = 0x00;
+const REGISTER_INNER: u8 = 0x01;
+const REGISTER_MODE: u8 = 0x80;
+const REGISTER_OUTER: u8 = 0x81;
+const SELECT_MASK: u8 = 0x81;
+
+const CHR_BANK_MASK: u8 = 0x03;
+const INNER_BANK_MASK: u8 = 0x0F;
+const SCREEN_SELECT: u8 = 0x10;
+const MIRRORING_MASK: u8 = 0x03;
+const MIRRORING_FIXED: u8 = 0x02;
+const PRG_MODE_SHIFT: u8 = 2;
+const GAME_SIZE_SHIFT: u8 = 4;
+const UNROM_MODES: u8 = 0x02;
+
+/// The Action 53 multicart board (mapper 28). An outer bank register picks a
+/// game-sized block of PRG and the inner register banks within it, emulating
+/// NROM, CNROM, UNROM, AOROM and BNROM style games.
+pub struct Action53 {
+    pub prg_rom: Vec<u8>,
+    pub chr: Chr,
+    pub prg_banks: Banks,
+    pub chr_banks: Banks,
+    pub select: u8,
+    pub inner: u8,
+    pub mode: u8,
+    pub outer: u8,
+}
+
+impl Mapper for Action53 {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        if address < PRG_BEGIN {
+            return 0;
+        }
+        let index = self.prg_banks.map(address - PRG_BEGIN);
+        return self.prg_rom[index];
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        match address {
+            SELECT_BEGIN..=SELECT_END => self.select = value & SELECT_MASK,
+            PRG_BEGIN..=0xFFFF => self.register_write(value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        let index = self.chr_banks.map(address);
+        return self.chr.read(index);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        let index = self.chr_banks.map(address);
+        self.chr.write(index, value);
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn mirroring(&self) -> Option<VramMirror> {
+        let result = match self.mode & MIRRORING_MASK {
+            0 => VramMirror::SingleScreenA,
+            1 => VramMirror::SingleScreenB,
+            2 => VramMirror::Vertical,
+            _ => VramMirror::Horizontal,
+        };
+        return Some(result);
+    }
+}
+
+impl Action53 {
+    pub const NUMBER: u8 = 28;
+    pub const CHR_RAM_SIZE: usize = 0x8000;
+
+    pub fn new(prg_rom: Vec<u8>, chr: Chr) -> Self {
+        let mut result = Self {
+            prg_banks: Banks::new(prg_rom.len(), PRG_WINDOW_SIZE, NUM_PRG_WINDOWS),
+            chr_banks: Banks::new(chr.len(), CHR_WINDOW_SIZE, 1),
+            prg_rom: prg_rom,
+            chr: chr,
+            select: 0,
+            inner: 0,
+            mode: 0,
+            outer: 0xFF,
+        };
+        result.update_prg_banks();
+        return result;
+    }
+
+    fn register_write(&mut self, value: u8) {
+        match self.select {
+            REGISTER_CHR => {
+                self.chr_banks.set(0, (value & CHR_BANK_MASK) as usize);
+                self.screen_write(value);
+            }
+            REGISTER_INNER => {
+                self.inner = value & INNER_BANK_MASK;
+                self.screen_write(value);
+            }
+            REGISTER_MODE => self.mode = value,
+            REGISTER_OUTER => self.outer = value,
+            _ => {}
+        }
+        self.update_prg_banks();
+    }
+
+    fn screen_write(&mut self, value: u8) {
+        if self.mode & MIRRORING_FIXED == 0 {
+            let screen = (value & SCREEN_SELECT) >> 4;
+            self.mode = (self.mode & !MIRRORING_MASK) | screen;
+        }
+    }
+
+    fn update_prg_banks(&mut self) {
+        for window in 0..NUM_PRG_WINDOWS {
+            let bank = self.prg_bank(window);
+            self.prg_banks.set(window, bank);
+        }
+    }
+
+    fn prg_bank(&self, cpu_a14: usize) -> usize {
+        let prg_mode = ((self.mode >> PRG_MODE_SHIFT) & 0x03) as usize;
+        let game_size = ((self.mode >> GAME_SIZE_SHIFT) & 0x03) as usize;
+        let outer = (self.outer as usize) << 1;
+        let is_fixed = (prg_mode ^ cpu_a14) & 0x03 == UNROM_MODES as usize;
+        if is_fixed {
+            return outer | cpu_a14;
+        }
+        let inner = if prg_mode & UNROM_MODES as usize == 0 {
+            ((self.inner as usize) << 1) | cpu_a14
+        } else {
+            self.inner as usize
+        };
+        let inner_mask = (2 << game_size) - 1;
+        return (outer & !inner_mask) | (inner & inner_mask);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::nes::cart::{
+    banks::Banks,
+    chr::Chr,
+    eeprom::{Eeprom, EepromChip},
+    prg_ram::PrgRam,
+};
+use crate::nes::{memory::Memory, ppu::VramMirror};
+
+use super::Mapper;
+
+const PRG_WINDOW_SIZE: usize = 0x4000;
+const NUM_PRG_WINDOWS: usize = 2;
+const CHR_WINDOW_SIZE: usize = 0x400;
+const NUM_CHR_WINDOWS: usize = 8;
+const OUTER_BANK_SIZE: usize = 0x40000;
+
+const RAM_BEGIN: usize = 0x6000;
+const RAM_END: usize = 0x7FFF;
+const PRG_BEGIN: usize = 0x8000;
+const REGISTER_MASK: usize = 0x0F;
+
+const REGISTER_CHR_END: usize = 0x07;
+const REGISTER_PRG: usize = 0x08;
+const REGISTER_MIRRORING: usize = 0x09;
+const REGISTER_IRQ_CONTROL: usize = 0x0A;
+const REGISTER_IRQ_LOW: usize = 0x0B;
+const REGISTER_IRQ_HIGH: usize = 0x0C;
+const REGISTER_EEPROM: usize = 0x0D;
+
+const PRG_BANK_MASK: u8 = 0x0F;
+const IRQ_ENABLE: u8 = 0x01;
+const EEPROM_SCL: u8 = 0x20;
+const EEPROM_SDA: u8 = 0x40;
+const EEPROM_DATA_OUT: u8 = 0x10;
+const RAM_ENABLE: u8 = 0x20;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BandaiBoard {
+    Fcg,
+    Lz93d50Sram,
+    Lz93d50Eeprom,
+}
+
+/// Bandai FCG-1/FCG-2 and LZ93D50 boards. Mapper 16 covers both register
+/// layouts with a 24C02 EEPROM, mapper 159 is the LZ93D50 with a 24C01, and
+/// mapper 153 swaps the EEPROM for battery-backed PRG-RAM and uses the low CHR
+/// register bit as a 256 KiB outer PRG bank.
+pub struct BandaiFcg {
+    pub board: BandaiBoard,
+    pub prg_rom: Vec<u8>,
+    pub chr: Chr,
+    pub prg_ram: PrgRam,
+    pub prg_banks: Banks,
+    pub chr_banks: Banks,
+    pub prg_bank: u8,
+    pub outer_bank: usize,
+    pub mirroring: u8,
+    pub irq_control: u8,
+    pub irq_latch: u16,
+    pub irq_counter: u16,
+    pub is_irq: bool,
+    pub eeprom: Option<Eeprom>,
+    pub eeprom_control: u8,
+}
+
+impl Mapper for BandaiFcg {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        let result = match address {
+            RAM_BEGIN..=RAM_END => self.low_read(address),
+            PRG_BEGIN..=0xFFFF => {
+                let index = self.prg_banks.map(address - PRG_BEGIN);
+                self.prg_rom[index]
+            }
+            _ => 0,
+        };
+        return result;
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        match address {
+            RAM_BEGIN..=RAM_END if self.board == BandaiBoard::Lz93d50Sram => {
+                if self.eeprom_control & RAM_ENABLE != 0 {
+                    self.prg_ram.write(address, value);
+                }
+            }
+            RAM_BEGIN..=RAM_END if self.board == BandaiBoard::Fcg => {
+                self.register_write(address & REGISTER_MASK, value, true)
+            }
+            PRG_BEGIN..=0xFFFF => self.register_write(address & REGISTER_MASK, value, false),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        let index = self.chr_banks.map(address);
+        return self.chr.read(index);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        let index = self.chr_banks.map(address);
+        self.chr.write(index, value);
+    }
+
+    fn tick(&mut self, n_cycles: i64) {
+        if self.irq_control & IRQ_ENABLE == 0 {
+            return;
+        }
+        for _ in 0..n_cycles {
+            if self.irq_counter == 0 {
+                self.is_irq = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+    }
+
+    fn is_irq(&self) -> bool {
+        return self.is_irq;
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn mirroring(&self) -> Option<VramMirror> {
+        let result = match self.mirroring {
+            0 => VramMirror::Vertical,
+            1 => VramMirror::Horizontal,
+            2 => VramMirror::SingleScreenA,
+            _ => VramMirror::SingleScreenB,
+        };
+        return Some(result);
+    }
+
+    fn save_data(&mut self) -> Option<&mut [u8]> {
+        if let Some(eeprom) = &mut self.eeprom {
+            return Some(&mut eeprom.data);
+        }
+        return self.prg_ram.save_data();
+    }
+
+    fn poll_save_dirty(&mut self) -> bool {
+        let mut result = self.prg_ram.poll_dirty();
+        if let Some(eeprom) = &mut self.eeprom {
+            result |= eeprom.is_dirty;
+            eeprom.is_dirty = false;
+        }
+        return result;
+    }
+}
+
+impl BandaiFcg {
+    pub const NUMBER_FCG: u8 = 16;
+    pub const NUMBER_LZ93D50_SRAM: u8 = 153;
+    pub const NUMBER_LZ93D50_EEPROM: u8 = 159;
+
+    pub fn new(board: BandaiBoard, prg_rom: Vec<u8>, chr: Chr, prg_ram: PrgRam) -> Self {
+        let eeprom = match board {
+            BandaiBoard::Fcg => Some(Eeprom::new(EepromChip::C24C02)),
+            BandaiBoard::Lz93d50Eeprom => Some(Eeprom::new(EepromChip::X24C01)),
+            BandaiBoard::Lz93d50Sram => None,
+        };
+        let mut prg_ram = prg_ram;
+        prg_ram.is_battery = board == BandaiBoard::Lz93d50Sram;
+        let mut result = Self {
+            board: board,
+            prg_banks: Banks::new(prg_rom.len(), PRG_WINDOW_SIZE, NUM_PRG_WINDOWS),
+            chr_banks: Banks::new(chr.len(), CHR_WINDOW_SIZE, NUM_CHR_WINDOWS),
+            prg_rom: prg_rom,
+            chr: chr,
+            prg_ram: prg_ram,
+            prg_bank: 0,
+            outer_bank: 0,
+            mirroring: 0,
+            irq_control: 0,
+            irq_latch: 0,
+            irq_counter: 0,
+            is_irq: false,
+            eeprom: eeprom,
+            eeprom_control: 0,
+        };
+        result.update_prg_banks();
+        return result;
+    }
+
+    fn low_read(&mut self, address: usize) -> u8 {
+        if let Some(eeprom) = &self.eeprom {
+            return if eeprom.read() { EEPROM_DATA_OUT } else { 0 };
+        }
+        if self.eeprom_control & RAM_ENABLE == 0 {
+            return 0;
+        }
+        return self.prg_ram.read(address);
+    }
+
+    fn register_write(&mut self, register: usize, value: u8, is_fcg_layout: bool) {
+        match register {
+            0..=REGISTER_CHR_END if self.board == BandaiBoard::Lz93d50Sram => {
+                self.outer_bank = (value & 0x01) as usize;
+            }
+            0..=REGISTER_CHR_END => self.chr_banks.set(register, value as usize),
+            REGISTER_PRG => self.prg_bank = value & PRG_BANK_MASK,
+            REGISTER_MIRRORING => self.mirroring = value & 0x03,
+            REGISTER_IRQ_CONTROL => {
+                self.irq_control = value;
+                self.is_irq = false;
+                if !is_fcg_layout {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            REGISTER_IRQ_LOW => {
+                self.irq_latch = (self.irq_latch & 0xFF00) | value as u16;
+                if is_fcg_layout {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            REGISTER_IRQ_HIGH => {
+                self.irq_latch = (self.irq_latch & 0x00FF) | ((value as u16) << 8);
+                if is_fcg_layout {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            REGISTER_EEPROM => {
+                self.eeprom_control = value;
+                if let Some(eeprom) = &mut self.eeprom {
+                    eeprom.write(value & EEPROM_SCL != 0, value & EEPROM_SDA != 0);
+                }
+            }
+            _ => {}
+        }
+        self.update_prg_banks();
+    }
+
+    fn update_prg_banks(&mut self) {
+        let banks_per_outer = OUTER_BANK_SIZE / PRG_WINDOW_SIZE;
+        let outer = self.outer_bank * banks_per_outer;
+        self.prg_banks.set(0, outer + self.prg_bank as usize);
+        self.prg_banks.set(1, outer + banks_per_outer - 1);
+        if self.board != BandaiBoard::Lz93d50Sram {
+            self.prg_banks.set_last(1);
+        }
+    }
+}
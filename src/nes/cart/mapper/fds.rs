@@ -0,0 +1,395 @@
+use crate::nes::{
+    cart::{
+        audio::{fds::FdsAudio, ExpansionAudio},
+        chr::Chr,
+        disk::{self, DiskImage},
+    },
+    memory::Memory,
+    ppu::VramMirror,
+    timing::Timing,
+};
+
+use super::Mapper;
+
+const PRG_RAM_SIZE: usize = 0x8000;
+const CHR_RAM_SIZE: usize = 0x2000;
+
+const RAM_BEGIN: usize = 0x6000;
+const RAM_END: usize = 0xDFFF;
+const BIOS_BEGIN: usize = 0xE000;
+
+const TIMER_LOW: usize = 0x4020;
+const TIMER_HIGH: usize = 0x4021;
+const TIMER_CONTROL: usize = 0x4022;
+const IO_ENABLE: usize = 0x4023;
+const WRITE_DATA: usize = 0x4024;
+const DISK_CONTROL: usize = 0x4025;
+const DISK_STATUS: usize = 0x4030;
+const READ_DATA: usize = 0x4031;
+const DRIVE_STATUS: usize = 0x4032;
+const EXTERNAL_READ: usize = 0x4033;
+
+const TIMER_REPEAT: u8 = 0x01;
+const TIMER_ENABLE: u8 = 0x02;
+const DISK_IO_ENABLE: u8 = 0x01;
+const SOUND_IO_ENABLE: u8 = 0x02;
+const MOTOR_ON: u8 = 0x01;
+const TRANSFER_RESET: u8 = 0x02;
+const READ_MODE: u8 = 0x04;
+const HORIZONTAL_MIRRORING: u8 = 0x08;
+const CRC_CONTROL: u8 = 0x10;
+const DISK_READY: u8 = 0x40;
+const DISK_IRQ_ENABLE: u8 = 0x80;
+
+const STATUS_TIMER_IRQ: u8 = 0x01;
+const STATUS_TRANSFER: u8 = 0x02;
+const STATUS_END_OF_HEAD: u8 = 0x40;
+const DRIVE_NOT_INSERTED: u8 = 0x01;
+const DRIVE_NOT_READY: u8 = 0x02;
+const DRIVE_WRITE_PROTECTED: u8 = 0x04;
+const BATTERY_GOOD: u8 = 0x80;
+
+const MOTOR_SPIN_UP: i64 = 50000;
+const BYTE_CYCLES: i64 = 149;
+const DISK_INSERT_SECONDS: f64 = 1.0;
+
+/// Famicom Disk System RAM adapter. The BIOS sits at $E000 over 32 KiB of
+/// PRG-RAM, and the drive streams one byte of the current disk side to or from
+/// the data registers roughly every 149 CPU cycles once the motor is running.
+pub struct Fds {
+    pub bios: Vec<u8>,
+    pub prg_ram: Vec<u8>,
+    pub chr: Chr,
+    pub disk: DiskImage,
+    /// The disk image file as loaded and as it stands now. Saves are a diff
+    /// between the two, so they line up with the user's file.
+    pub original_file: Vec<u8>,
+    pub file: Vec<u8>,
+    pub side: Option<usize>,
+    pub pending_side: Option<usize>,
+    pub insert_delay: i64,
+    pub io_enable: u8,
+    pub control: u8,
+    pub timer_reload: u16,
+    pub timer_counter: u16,
+    pub timer_control: u8,
+    pub is_timer_irq: bool,
+    pub is_disk_irq: bool,
+    pub read_data: u8,
+    pub write_data: u8,
+    pub is_transfer_done: bool,
+    pub is_end_of_head: bool,
+    pub is_scanning: bool,
+    pub is_gap_ended: bool,
+    pub was_crc_control: bool,
+    pub crc: u16,
+    pub position: usize,
+    pub delay: i64,
+    pub is_disk_dirty: bool,
+    pub audio: FdsAudio,
+}
+
+impl Mapper for Fds {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        let result = match address {
+            DISK_STATUS => self.status_read(),
+            READ_DATA => {
+                self.is_transfer_done = false;
+                self.is_disk_irq = false;
+                self.read_data
+            }
+            DRIVE_STATUS => self.drive_status(),
+            EXTERNAL_READ => BATTERY_GOOD,
+            FdsAudio::BEGIN..=FdsAudio::END if self.io_enable & SOUND_IO_ENABLE != 0 => {
+                self.audio.read(address)
+            }
+            RAM_BEGIN..=RAM_END => self.prg_ram[address - RAM_BEGIN],
+            BIOS_BEGIN..=0xFFFF => self.bios[(address - BIOS_BEGIN) % self.bios.len()],
+            _ => 0,
+        };
+        return result;
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        match address {
+            TIMER_LOW => self.timer_reload = (self.timer_reload & 0xFF00) | value as u16,
+            TIMER_HIGH => self.timer_reload = (self.timer_reload & 0x00FF) | ((value as u16) << 8),
+            TIMER_CONTROL => self.timer_control_write(value),
+            IO_ENABLE => {
+                self.io_enable = value;
+                if value & DISK_IO_ENABLE == 0 {
+                    self.timer_control &= !TIMER_ENABLE;
+                    self.is_timer_irq = false;
+                    self.is_disk_irq = false;
+                }
+            }
+            WRITE_DATA => {
+                self.write_data = value;
+                self.is_transfer_done = false;
+                self.is_disk_irq = false;
+            }
+            DISK_CONTROL => {
+                self.control = value;
+                self.is_disk_irq = false;
+            }
+            FdsAudio::BEGIN..=FdsAudio::END if self.io_enable & SOUND_IO_ENABLE != 0 => {
+                self.audio.write(address, value)
+            }
+            RAM_BEGIN..=RAM_END => self.prg_ram[address - RAM_BEGIN] = value,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        return self.chr.read(address);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        self.chr.write(address, value);
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn mirroring(&self) -> Option<VramMirror> {
+        if self.control & HORIZONTAL_MIRRORING != 0 {
+            return Some(VramMirror::Horizontal);
+        }
+        return Some(VramMirror::Vertical);
+    }
+
+    fn tick(&mut self, n_cycles: i64) {
+        for _ in 0..n_cycles {
+            self.clock_timer();
+            self.clock_drive();
+        }
+        self.audio.tick(n_cycles);
+    }
+
+    fn is_irq(&self) -> bool {
+        return self.is_timer_irq || self.is_disk_irq;
+    }
+
+    fn expansion_audio(&self) -> f32 {
+        return self.audio.output();
+    }
+
+    fn save_data(&mut self) -> Option<&mut [u8]> {
+        self.disk.write_file(&mut self.file);
+        return Some(&mut self.file);
+    }
+
+    fn save_base(&self) -> Option<&[u8]> {
+        return Some(&self.original_file);
+    }
+
+    fn save_data_loaded(&mut self) {
+        if let Ok(disk) = DiskImage::new(&self.file) {
+            self.disk = disk;
+        }
+    }
+
+    fn poll_save_dirty(&mut self) -> bool {
+        let result = self.is_disk_dirty;
+        self.is_disk_dirty = false;
+        return result;
+    }
+
+    fn num_disk_sides(&self) -> usize {
+        return self.disk.num_sides;
+    }
+
+    fn insert_disk(&mut self, side: Option<usize>) {
+        self.side = None;
+        self.pending_side = side.filter(|&side| side < self.disk.num_sides);
+        // The Disk System was only sold for the NTSC Famicom.
+        self.insert_delay = (DISK_INSERT_SECONDS * Timing::default().cpu_frequency) as i64;
+    }
+}
+
+impl Fds {
+    pub fn new(bios: Vec<u8>, disk: DiskImage, file: Vec<u8>) -> Self {
+        return Self {
+            bios: bios,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr: Chr::new(vec![], CHR_RAM_SIZE),
+            disk: disk,
+            original_file: file.clone(),
+            file: file,
+            side: Some(0),
+            pending_side: None,
+            insert_delay: 0,
+            io_enable: 0,
+            control: 0,
+            timer_reload: 0,
+            timer_counter: 0,
+            timer_control: 0,
+            is_timer_irq: false,
+            is_disk_irq: false,
+            read_data: 0,
+            write_data: 0,
+            is_transfer_done: false,
+            is_end_of_head: true,
+            is_scanning: false,
+            is_gap_ended: false,
+            was_crc_control: false,
+            crc: 0,
+            position: 0,
+            delay: 0,
+            is_disk_dirty: false,
+            audio: Default::default(),
+        };
+    }
+
+    fn timer_control_write(&mut self, value: u8) {
+        self.timer_control = value;
+        if self.io_enable & DISK_IO_ENABLE == 0 {
+            self.timer_control &= !TIMER_ENABLE;
+        }
+        if self.timer_control & TIMER_ENABLE != 0 {
+            self.timer_counter = self.timer_reload;
+        } else {
+            self.is_timer_irq = false;
+        }
+    }
+
+    fn status_read(&mut self) -> u8 {
+        let mut result = 0;
+        if self.is_timer_irq {
+            result |= STATUS_TIMER_IRQ;
+        }
+        if self.is_transfer_done {
+            result |= STATUS_TRANSFER;
+        }
+        if self.is_end_of_head {
+            result |= STATUS_END_OF_HEAD;
+        }
+        self.is_transfer_done = false;
+        self.is_timer_irq = false;
+        self.is_disk_irq = false;
+        return result;
+    }
+
+    fn drive_status(&self) -> u8 {
+        let mut result = 0;
+        if self.side.is_none() {
+            result |= DRIVE_NOT_INSERTED | DRIVE_WRITE_PROTECTED;
+        }
+        if self.side.is_none() || !self.is_scanning {
+            result |= DRIVE_NOT_READY;
+        }
+        return result;
+    }
+
+    fn clock_timer(&mut self) {
+        if self.timer_control & TIMER_ENABLE == 0 {
+            return;
+        }
+        if self.timer_counter > 0 {
+            self.timer_counter -= 1;
+            return;
+        }
+        self.is_timer_irq = true;
+        self.timer_counter = self.timer_reload;
+        if self.timer_control & TIMER_REPEAT == 0 {
+            self.timer_control &= !TIMER_ENABLE;
+        }
+    }
+
+    fn clock_drive(&mut self) {
+        if self.insert_delay > 0 {
+            self.insert_delay -= 1;
+            if self.insert_delay == 0 {
+                self.side = self.pending_side.take();
+            }
+        }
+        let Some(side) = self.side else {
+            self.is_end_of_head = true;
+            self.is_scanning = false;
+            return;
+        };
+        if self.control & MOTOR_ON == 0 {
+            self.is_end_of_head = true;
+            self.is_scanning = false;
+            return;
+        }
+        if self.control & TRANSFER_RESET != 0 && !self.is_scanning {
+            return;
+        }
+        if self.is_end_of_head {
+            self.delay = MOTOR_SPIN_UP;
+            self.is_end_of_head = false;
+            self.position = 0;
+            self.is_gap_ended = false;
+            return;
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+        self.is_scanning = true;
+        let index = side * DiskImage::SIDE_CAPACITY + self.position;
+        if self.control & READ_MODE != 0 {
+            self.read_byte(index);
+        } else {
+            self.write_byte(index);
+        }
+        self.was_crc_control = self.control & CRC_CONTROL != 0;
+        self.position += 1;
+        if self.position >= DiskImage::SIDE_CAPACITY {
+            self.control &= !MOTOR_ON;
+            self.is_end_of_head = true;
+        } else {
+            self.delay = BYTE_CYCLES;
+        }
+    }
+
+    /// The first non-zero byte after the gap is the gap end marker. It starts
+    /// the transfer without raising an IRQ.
+    fn read_byte(&mut self, index: usize) {
+        let value = self.disk.data[index];
+        let mut is_irq = self.control & DISK_IRQ_ENABLE != 0;
+        if self.control & DISK_READY == 0 {
+            self.is_gap_ended = false;
+        } else if value != 0 && !self.is_gap_ended {
+            self.is_gap_ended = true;
+            is_irq = false;
+        }
+        if self.is_gap_ended {
+            self.is_transfer_done = true;
+            self.read_data = value;
+            self.is_disk_irq |= is_irq;
+        }
+    }
+
+    /// While CRC control is set the drive writes the accumulated CRC instead
+    /// of the data register.
+    fn write_byte(&mut self, index: usize) {
+        let is_crc_control = self.control & CRC_CONTROL != 0;
+        let mut value = 0;
+        if !is_crc_control {
+            self.is_transfer_done = true;
+            value = self.write_data;
+            self.is_disk_irq |= self.control & DISK_IRQ_ENABLE != 0;
+        }
+        if self.control & DISK_READY == 0 {
+            value = 0;
+        }
+        if !is_crc_control {
+            self.crc = disk::update_crc(self.crc, value);
+        } else {
+            if !self.was_crc_control {
+                self.crc = disk::update_crc(self.crc, 0);
+                self.crc = disk::update_crc(self.crc, 0);
+            }
+            value = self.crc as u8;
+            self.crc >>= 8;
+        }
+        if self.disk.data[index] != value {
+            self.disk.data[index] = value;
+            self.is_disk_dirty = true;
+        }
+        self.is_gap_ended = false;
+    }
+}
@@ -0,0 +1,130 @@
+use crate::nes::cart::{banks::Banks, chr::Chr, flash::Flash};
+use crate::nes::memory::Memory;
+
+use super::Mapper;
+
+const PRG_WINDOW_SIZE: usize = 0x8000;
+const CHR_WINDOW_SIZE: usize = 0x2000;
+const NAMETABLE_WINDOW_SIZE: usize = 0x2000;
+const NAMETABLE_RAM_SIZE: usize = 0x4000;
+const NAMETABLE_MASK: usize = 0x1FFF;
+
+const REGISTER_BEGIN: usize = 0x5000;
+const REGISTER_END: usize = 0x5FFF;
+const REGISTER_MIRROR_BEGIN: usize = 0x7000;
+const REGISTER_MIRROR_END: usize = 0x7FFF;
+const PRG_BEGIN: usize = 0x8000;
+
+const PRG_BANK_MASK: u8 = 0x0F;
+const CHR_BANK_SELECT: u8 = 0x10;
+const NAMETABLE_BANK_SELECT: u8 = 0x20;
+const LED_GREEN_OFF: u8 = 0x40;
+const LED_RED_OFF: u8 = 0x80;
+
+/// Membler Industries GTROM, a.k.a. Cheapocabra (mapper 111). 32 KiB PRG
+/// banks out of self-flashable memory, two pages of CHR-RAM and two pages of
+/// four-screen nametable RAM, plus a pair of LEDs on the cartridge.
+pub struct Gtrom {
+    pub prg_rom: Vec<u8>,
+    pub chr: Chr,
+    pub nametable_ram: Vec<u8>,
+    pub prg_banks: Banks,
+    pub chr_banks: Banks,
+    pub nametable_banks: Banks,
+    pub is_green_led_on: bool,
+    pub is_red_led_on: bool,
+    pub flash: Flash,
+}
+
+impl Mapper for Gtrom {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        if address < PRG_BEGIN {
+            return 0;
+        }
+        let index = self.prg_banks.map(address - PRG_BEGIN);
+        return self.flash.read(&self.prg_rom, index);
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        match address {
+            REGISTER_BEGIN..=REGISTER_END | REGISTER_MIRROR_BEGIN..=REGISTER_MIRROR_END => {
+                self.register_write(value)
+            }
+            PRG_BEGIN..=0xFFFF => {
+                let index = self.prg_banks.map(address - PRG_BEGIN);
+                self.flash.write(&mut self.prg_rom, index, value);
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        let index = self.chr_banks.map(address);
+        return self.chr.read(index);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        let index = self.chr_banks.map(address);
+        self.chr.write(index, value);
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn nametable_read(&mut self, address: usize) -> Option<u8> {
+        let index = self.nametable_banks.map(address & NAMETABLE_MASK);
+        return Some(self.nametable_ram[index]);
+    }
+
+    fn nametable_write(&mut self, address: usize, value: u8) -> bool {
+        let index = self.nametable_banks.map(address & NAMETABLE_MASK);
+        self.nametable_ram[index] = value;
+        return true;
+    }
+
+    fn save_data(&mut self) -> Option<&mut [u8]> {
+        return Some(&mut self.prg_rom);
+    }
+
+    fn poll_save_dirty(&mut self) -> bool {
+        let result = self.flash.is_dirty;
+        self.flash.is_dirty = false;
+        return result;
+    }
+}
+
+impl Gtrom {
+    pub const NUMBER: u8 = 111;
+    pub const CHR_RAM_SIZE: usize = 0x4000;
+
+    pub fn new(prg_rom: Vec<u8>, chr: Chr) -> Self {
+        let mut prg_banks = Banks::new(prg_rom.len(), PRG_WINDOW_SIZE, 1);
+        prg_banks.set(0, 0);
+        return Self {
+            prg_rom: prg_rom,
+            chr_banks: Banks::new(chr.len(), CHR_WINDOW_SIZE, 1),
+            chr: chr,
+            nametable_ram: vec![0; NAMETABLE_RAM_SIZE],
+            prg_banks: prg_banks,
+            nametable_banks: Banks::new(NAMETABLE_RAM_SIZE, NAMETABLE_WINDOW_SIZE, 1),
+            is_green_led_on: true,
+            is_red_led_on: true,
+            flash: Default::default(),
+        };
+    }
+
+    fn register_write(&mut self, value: u8) {
+        self.prg_banks.set(0, (value & PRG_BANK_MASK) as usize);
+        let chr_bank = if value & CHR_BANK_SELECT != 0 { 1 } else { 0 };
+        self.chr_banks.set(0, chr_bank);
+        let nametable_bank = if value & NAMETABLE_BANK_SELECT != 0 {
+            1
+        } else {
+            0
+        };
+        self.nametable_banks.set(0, nametable_bank);
+        self.is_green_led_on = value & LED_GREEN_OFF == 0;
+        self.is_red_led_on = value & LED_RED_OFF == 0;
+    }
+}
@@ -0,0 +1,77 @@
+use crate::nes::{
+    cart::{chr::Chr, prg_ram::PrgRam},
+    memory::Memory,
+};
+
+use super::Mapper;
+
+const PRG_BEGIN: usize = 0x8000;
+
+pub struct Nrom {
+    pub prg_rom: Vec<u8>,
+    pub chr: Chr,
+    pub prg_ram: PrgRam,
+}
+
+impl Default for Nrom {
+    fn default() -> Self {
+        Self {
+            prg_rom: Default::default(),
+            chr: Default::default(),
+            prg_ram: Default::default(),
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        let result = match address {
+            PrgRam::BEGIN..=PrgRam::END => self.prg_ram.read(address),
+            PRG_BEGIN..=0xFFFF => {
+                let address = (address - PRG_BEGIN) % self.prg_rom.len();
+                self.prg_rom[address]
+            }
+            _ => 0,
+        };
+        return result;
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        match address {
+            PrgRam::BEGIN..=PrgRam::END => self.prg_ram.write(address, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        return self.chr.read(address);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        self.chr.write(address, value);
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn save_data(&mut self) -> Option<&mut [u8]> {
+        return self.prg_ram.save_data();
+    }
+
+    fn poll_save_dirty(&mut self) -> bool {
+        return self.prg_ram.poll_dirty();
+    }
+}
+
+impl Nrom {
+    pub const NUMBER: u8 = 0;
+
+    pub fn new(prg_rom: Vec<u8>, chr: Chr, prg_ram: PrgRam) -> Self {
+        return Self {
+            prg_rom: prg_rom,
+            chr: chr,
+            prg_ram: prg_ram,
+        };
+    }
+}
@@ -0,0 +1,369 @@
+use crate::nes::{
+    cart::{
+        audio::{fds::FdsAudio, sunsoft_5b::Sunsoft5b, ExpansionAudio},
+        banks::Banks,
+        chr::Chr,
+        header::Region,
+        nsf::{Nsf, NsfExpansion},
+    },
+    memory::Memory,
+    timing::Timing,
+};
+
+use super::Mapper;
+
+const BANK_SIZE: usize = 0x1000;
+const NUM_WINDOWS: usize = 8;
+const NUM_FDS_WINDOWS: usize = 10;
+const RAM_SIZE: usize = 0x2000;
+const CHR_RAM_SIZE: usize = 0x2000;
+
+const MICROSECONDS_PER_SECOND: f64 = 1000000.0;
+
+const RAM_BEGIN: usize = 0x6000;
+const RAM_END: usize = 0x7FFF;
+const PRG_BEGIN: usize = 0x8000;
+const VECTORS_BEGIN: usize = 0xFFFA;
+const BANK_REGISTER_BEGIN: usize = 0x5FF8;
+const FDS_BANK_REGISTER_BEGIN: usize = 0x5FF6;
+const BANK_REGISTER_END: usize = 0x5FFF;
+
+const DRIVER_BEGIN: usize = 0x4F00;
+const DRIVER_END: usize = 0x4FEF;
+const TRACK_REGISTER: usize = 0x4FF0;
+const REGION_REGISTER: usize = 0x4FF1;
+const PLAY_START_REGISTER: usize = 0x4FF2;
+const PLAY_ACK_REGISTER: usize = 0x4FF3;
+
+/// A cartridge that exists only to play an NSF rip. A small driver mapped at
+/// $4F00 clears RAM, calls INIT with the selected track and then idles while
+/// a cycle timer raises an IRQ whose handler calls PLAY. The CPU vectors are
+/// overridden to point at the driver.
+pub struct NsfMapper {
+    pub prg_rom: Vec<u8>,
+    pub prg_banks: Banks,
+    pub initial_banks: Vec<usize>,
+    pub ram: Vec<u8>,
+    pub chr: Chr,
+    pub driver: Vec<u8>,
+    pub vectors: [u8; 6],
+    pub is_fds: bool,
+    pub track: u8,
+    pub region: u8,
+    pub play_period: i64,
+    pub play_counter: i64,
+    pub is_playing: bool,
+    pub is_irq: bool,
+    pub fds_audio: Option<FdsAudio>,
+    pub sunsoft_5b: Option<Sunsoft5b>,
+}
+
+impl Mapper for NsfMapper {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        let result = match address {
+            DRIVER_BEGIN..=DRIVER_END => {
+                let index = address - DRIVER_BEGIN;
+                self.driver.get(index).copied().unwrap_or(0)
+            }
+            TRACK_REGISTER => self.track,
+            REGION_REGISTER => self.region,
+            FdsAudio::BEGIN..=FdsAudio::END => match &mut self.fds_audio {
+                Some(audio) => audio.read(address),
+                None => 0,
+            },
+            VECTORS_BEGIN..=0xFFFF => self.vectors[address - VECTORS_BEGIN],
+            RAM_BEGIN..=0xFFFF if self.is_fds => self.ram[address - RAM_BEGIN],
+            RAM_BEGIN..=RAM_END => self.ram[address - RAM_BEGIN],
+            PRG_BEGIN..=0xFFFF => {
+                let index = self.prg_banks.map(address - PRG_BEGIN);
+                self.prg_rom[index]
+            }
+            _ => 0,
+        };
+        return result;
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        match address {
+            PLAY_START_REGISTER => self.is_playing = true,
+            PLAY_ACK_REGISTER => self.is_irq = false,
+            FDS_BANK_REGISTER_BEGIN..=BANK_REGISTER_END => self.bank_write(address, value),
+            FdsAudio::BEGIN..=FdsAudio::END => {
+                if let Some(audio) = &mut self.fds_audio {
+                    audio.write(address, value);
+                }
+            }
+            RAM_BEGIN..=0xDFFF if self.is_fds => self.ram[address - RAM_BEGIN] = value,
+            RAM_BEGIN..=RAM_END => self.ram[address - RAM_BEGIN] = value,
+            Sunsoft5b::SELECT_BEGIN..=Sunsoft5b::WRITE_END => {
+                if let Some(audio) = &mut self.sunsoft_5b {
+                    audio.write(address, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        return self.chr.read(address);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        self.chr.write(address, value);
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn tick(&mut self, n_cycles: i64) {
+        if let Some(audio) = &mut self.fds_audio {
+            audio.tick(n_cycles);
+        }
+        if let Some(audio) = &mut self.sunsoft_5b {
+            audio.tick(n_cycles);
+        }
+        if !self.is_playing {
+            return;
+        }
+        self.play_counter -= n_cycles;
+        if self.play_counter <= 0 {
+            self.play_counter += self.play_period;
+            self.is_irq = true;
+        }
+    }
+
+    fn is_irq(&self) -> bool {
+        return self.is_irq;
+    }
+
+    fn expansion_audio(&self) -> f32 {
+        let mut result = 0.0;
+        if let Some(audio) = &self.fds_audio {
+            result += audio.output();
+        }
+        if let Some(audio) = &self.sunsoft_5b {
+            result += audio.output();
+        }
+        return result;
+    }
+
+    fn select_track(&mut self, track: usize) {
+        self.track = track as u8;
+        self.is_playing = false;
+        self.is_irq = false;
+        self.play_counter = self.play_period;
+        self.ram.fill(0);
+        for (window, &bank) in self.initial_banks.clone().iter().enumerate() {
+            self.set_bank(window, bank);
+        }
+        if let Some(audio) = &mut self.fds_audio {
+            *audio = Default::default();
+        }
+        if let Some(audio) = &mut self.sunsoft_5b {
+            *audio = Default::default();
+        }
+    }
+}
+
+impl NsfMapper {
+    pub fn new(nsf: &Nsf) -> Self {
+        let is_fds = nsf.expansion.contains(NsfExpansion::Fds);
+        let (prg_rom, initial_banks) = Self::layout(nsf, is_fds);
+        let num_windows = if is_fds { NUM_FDS_WINDOWS } else { NUM_WINDOWS };
+        let ram_size = if is_fds {
+            NUM_FDS_WINDOWS * BANK_SIZE
+        } else {
+            RAM_SIZE
+        };
+        // INIT is only told NTSC or PAL. Dendy runs at 50 Hz like PAL, just
+        // with a faster CPU clock.
+        let is_pal = nsf.region == Region::Pal || nsf.region == Region::Dendy;
+        let cycles_per_second = Timing::new(nsf.region).cpu_frequency;
+        let play_period =
+            (nsf.play_speed() as f64 * cycles_per_second / MICROSECONDS_PER_SECOND) as i64;
+        let (driver, vectors) = driver(nsf.init_address, nsf.play_address);
+        let mut result = Self {
+            prg_banks: Banks::new(prg_rom.len(), BANK_SIZE, num_windows),
+            prg_rom: prg_rom,
+            initial_banks: initial_banks,
+            ram: vec![0; ram_size],
+            chr: Chr::new(vec![], CHR_RAM_SIZE),
+            driver: driver,
+            vectors: vectors,
+            is_fds: is_fds,
+            track: nsf.starting_track as u8,
+            region: if is_pal { 1 } else { 0 },
+            play_period: play_period.max(1),
+            play_counter: 0,
+            is_playing: false,
+            is_irq: false,
+            fds_audio: if is_fds {
+                Some(Default::default())
+            } else {
+                None
+            },
+            sunsoft_5b: if nsf.expansion.contains(NsfExpansion::Sunsoft5b) {
+                Some(Default::default())
+            } else {
+                None
+            },
+        };
+        result.select_track(nsf.starting_track);
+        return result;
+    }
+
+    /// Expansion chips the rip uses that we can't play.
+    pub fn unsupported_expansion(nsf: &Nsf) -> NsfExpansion {
+        return nsf.expansion & !(NsfExpansion::Fds | NsfExpansion::Sunsoft5b);
+    }
+
+    /// Lays the NSF data out in 4 KiB banks. Bankswitched rips are padded by
+    /// the low bits of the load address and start in the banks from the
+    /// header. Others are placed at the load address with banks in order.
+    fn layout(nsf: &Nsf, is_fds: bool) -> (Vec<u8>, Vec<usize>) {
+        let base = if is_fds { RAM_BEGIN } else { PRG_BEGIN };
+        let num_windows = if is_fds { NUM_FDS_WINDOWS } else { NUM_WINDOWS };
+        let (padding, banks) = if nsf.is_bankswitched() {
+            let padding = nsf.load_address as usize & (BANK_SIZE - 1);
+            let mut banks: Vec<usize> = nsf.banks.iter().map(|&bank| bank as usize).collect();
+            if is_fds {
+                // $6000-$7FFF start out with the same banks as $E000-$FFFF.
+                let mut fds_banks = vec![banks[6], banks[7]];
+                fds_banks.append(&mut banks);
+                banks = fds_banks;
+            }
+            (padding, banks)
+        } else {
+            let padding = (nsf.load_address as usize).saturating_sub(base);
+            (padding, (0..num_windows).collect())
+        };
+        let mut prg_rom = vec![0; padding];
+        prg_rom.extend_from_slice(&nsf.data);
+        let size = prg_rom.len().div_ceil(BANK_SIZE).max(num_windows) * BANK_SIZE;
+        prg_rom.resize(size, 0);
+        return (prg_rom, banks);
+    }
+
+    fn bank_write(&mut self, address: usize, value: u8) {
+        if self.is_fds {
+            self.set_bank(address - FDS_BANK_REGISTER_BEGIN, value as usize);
+        } else if address >= BANK_REGISTER_BEGIN {
+            self.set_bank(address - BANK_REGISTER_BEGIN, value as usize);
+        }
+    }
+
+    /// FDS rips run from RAM, so switching a bank copies it in rather than
+    /// remapping the window.
+    fn set_bank(&mut self, window: usize, bank: usize) {
+        self.prg_banks.set(window, bank);
+        if self.is_fds {
+            let source = self.prg_banks.map(window * BANK_SIZE);
+            let target = window * BANK_SIZE;
+            let bank = &self.prg_rom[source..source + BANK_SIZE];
+            self.ram[target..target + BANK_SIZE].copy_from_slice(bank);
+        }
+    }
+}
+
+/// Assembles the driver and the vectors pointing into it. Reset clears the
+/// console RAM, silences the APU and calls INIT; the IRQ handler acknowledges
+/// the play timer and calls PLAY; NMI just returns.
+fn driver(init_address: u16, play_address: u16) -> (Vec<u8>, [u8; 6]) {
+    let [init_low, init_high] = init_address.to_le_bytes();
+    let [play_low, play_high] = play_address.to_le_bytes();
+    let [track_low, track_high] = (TRACK_REGISTER as u16).to_le_bytes();
+    let [region_low, region_high] = (REGION_REGISTER as u16).to_le_bytes();
+    let [start_low, start_high] = (PLAY_START_REGISTER as u16).to_le_bytes();
+    let [ack_low, ack_high] = (PLAY_ACK_REGISTER as u16).to_le_bytes();
+    let mut result = vec![
+        0x78, // SEI
+        0xD8, // CLD
+        0xA2,
+        0xFF, // LDX #$FF
+        0x9A, // TXS
+        0xA9,
+        0x00, // LDA #$00
+        0xAA, // TAX
+        0x95,
+        0x00, // clear: STA $00,X
+        0x9D,
+        0x00,
+        0x01, // STA $0100,X
+        0x9D,
+        0x00,
+        0x02, // STA $0200,X
+        0x9D,
+        0x00,
+        0x03, // STA $0300,X
+        0x9D,
+        0x00,
+        0x04, // STA $0400,X
+        0x9D,
+        0x00,
+        0x05, // STA $0500,X
+        0x9D,
+        0x00,
+        0x06, // STA $0600,X
+        0x9D,
+        0x00,
+        0x07, // STA $0700,X
+        0xE8, // INX
+        0xD0,
+        0xE6, // BNE clear
+        0x8D,
+        0x15,
+        0x40, // STA $4015
+        0xA9,
+        0x0F, // LDA #$0F
+        0x8D,
+        0x15,
+        0x40, // STA $4015
+        0xA9,
+        0x40, // LDA #$40
+        0x8D,
+        0x17,
+        0x40, // STA $4017
+        0xAD,
+        track_low,
+        track_high, // LDA track
+        0xAE,
+        region_low,
+        region_high, // LDX region
+        0x20,
+        init_low,
+        init_high, // JSR INIT
+        0x8D,
+        start_low,
+        start_high, // STA play start
+        0x58,       // CLI
+    ];
+    let idle = (DRIVER_BEGIN + result.len()) as u16;
+    let [idle_low, idle_high] = idle.to_le_bytes();
+    result.extend_from_slice(&[0x4C, idle_low, idle_high]); // idle: JMP idle
+
+    let irq = (DRIVER_BEGIN + result.len()) as u16;
+    result.extend_from_slice(&[
+        0x48, // PHA
+        0x8A, // TXA
+        0x48, // PHA
+        0x98, // TYA
+        0x48, // PHA
+        0x8D, ack_low, ack_high, // STA play ack
+        0x20, play_low, play_high, // JSR PLAY
+        0x68,      // PLA
+        0xA8,      // TAY
+        0x68,      // PLA
+        0xAA,      // TAX
+        0x68,      // PLA
+        0x40,      // RTI
+    ]);
+    let nmi = (DRIVER_BEGIN + result.len()) as u16;
+    result.push(0x40); // RTI
+
+    let [nmi_low, nmi_high] = nmi.to_le_bytes();
+    let [reset_low, reset_high] = (DRIVER_BEGIN as u16).to_le_bytes();
+    let [irq_low, irq_high] = irq.to_le_bytes();
+    let vectors = [nmi_low, nmi_high, reset_low, reset_high, irq_low, irq_high];
+    return (result, vectors);
+}
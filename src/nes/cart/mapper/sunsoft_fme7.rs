@@ -0,0 +1,248 @@
+use crate::nes::{
+    cart::{
+        audio::{sunsoft_5b::Sunsoft5b, ExpansionAudio},
+        banks::Banks,
+        chr::Chr,
+        prg_ram::PrgRam,
+    },
+    memory::Memory,
+    ppu::VramMirror,
+};
+
+use super::Mapper;
+
+const PRG_WINDOW_SIZE: usize = 0x2000;
+const CHR_WINDOW_SIZE: usize = 0x400;
+const NUM_PRG_WINDOWS: usize = 4;
+const NUM_CHR_WINDOWS: usize = 8;
+
+const RAM_BEGIN: usize = 0x6000;
+const RAM_END: usize = 0x7FFF;
+const COMMAND_BEGIN: usize = 0x8000;
+const COMMAND_END: usize = 0x9FFF;
+const PARAMETER_BEGIN: usize = 0xA000;
+const PARAMETER_END: usize = 0xBFFF;
+const PRG_BEGIN: usize = 0x8000;
+
+const COMMAND_CHR_END: u8 = 0x07;
+const COMMAND_PRG_RAM: u8 = 0x08;
+const COMMAND_PRG_BEGIN: u8 = 0x09;
+const COMMAND_PRG_END: u8 = 0x0B;
+const COMMAND_MIRRORING: u8 = 0x0C;
+const COMMAND_IRQ_CONTROL: u8 = 0x0D;
+const COMMAND_IRQ_LOW: u8 = 0x0E;
+const COMMAND_IRQ_HIGH: u8 = 0x0F;
+
+const RAM_ENABLE: u8 = 0x80;
+const RAM_SELECT: u8 = 0x40;
+const BANK_MASK: u8 = 0x3F;
+const IRQ_ENABLE: u8 = 0x01;
+const IRQ_COUNTER_ENABLE: u8 = 0x80;
+
+/// Sunsoft FME-7 (mapper 69). Also covers the 5B, which is the same banking
+/// logic with an extra sound core answering at $C000-$FFFF.
+pub struct SunsoftFme7 {
+    pub prg_rom: Vec<u8>,
+    pub chr: Chr,
+    pub prg_ram: PrgRam,
+    pub prg_banks: Banks,
+    pub chr_banks: Banks,
+    pub command: u8,
+    pub ram_control: u8,
+    pub mirroring: u8,
+    pub irq_control: u8,
+    pub irq_counter: u16,
+    pub is_irq: bool,
+    pub audio: Sunsoft5b,
+}
+
+impl Mapper for SunsoftFme7 {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        let result = match address {
+            RAM_BEGIN..=RAM_END => self.low_bank_read(address),
+            PRG_BEGIN..=0xFFFF => {
+                let index = self.prg_banks.map(address - PRG_BEGIN);
+                self.prg_rom[index]
+            }
+            _ => 0,
+        };
+        return result;
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        match address {
+            RAM_BEGIN..=RAM_END => self.low_bank_write(address, value),
+            COMMAND_BEGIN..=COMMAND_END => self.command = value & 0x0F,
+            PARAMETER_BEGIN..=PARAMETER_END => self.parameter_write(value),
+            Sunsoft5b::SELECT_BEGIN..=Sunsoft5b::WRITE_END => self.audio.write(address, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        let index = self.chr_banks.map(address);
+        return self.chr.read(index);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        let index = self.chr_banks.map(address);
+        self.chr.write(index, value);
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn tick(&mut self, n_cycles: i64) {
+        self.audio.tick(n_cycles);
+        if self.irq_control & IRQ_COUNTER_ENABLE == 0 {
+            return;
+        }
+        for _ in 0..n_cycles {
+            let (result, is_underflow) = self.irq_counter.overflowing_sub(1);
+            self.irq_counter = result;
+            if is_underflow && self.irq_control & IRQ_ENABLE != 0 {
+                self.is_irq = true;
+            }
+        }
+    }
+
+    fn is_irq(&self) -> bool {
+        return self.is_irq;
+    }
+
+    fn expansion_audio(&self) -> f32 {
+        return self.audio.output();
+    }
+
+    fn mirroring(&self) -> Option<VramMirror> {
+        let result = match self.mirroring {
+            0 => VramMirror::Vertical,
+            1 => VramMirror::Horizontal,
+            2 => VramMirror::SingleScreenA,
+            _ => VramMirror::SingleScreenB,
+        };
+        return Some(result);
+    }
+
+    fn save_data(&mut self) -> Option<&mut [u8]> {
+        return self.prg_ram.save_data();
+    }
+
+    fn poll_save_dirty(&mut self) -> bool {
+        return self.prg_ram.poll_dirty();
+    }
+}
+
+impl SunsoftFme7 {
+    pub const NUMBER: u8 = 69;
+
+    pub fn new(prg_rom: Vec<u8>, chr: Chr, prg_ram: PrgRam) -> Self {
+        let mut prg_banks = Banks::new(prg_rom.len(), PRG_WINDOW_SIZE, NUM_PRG_WINDOWS);
+        prg_banks.set_last(NUM_PRG_WINDOWS - 1);
+        let chr_banks = Banks::new(chr.len(), CHR_WINDOW_SIZE, NUM_CHR_WINDOWS);
+        return Self {
+            prg_rom: prg_rom,
+            chr: chr,
+            prg_ram: prg_ram,
+            prg_banks: prg_banks,
+            chr_banks: chr_banks,
+            command: 0,
+            ram_control: 0,
+            mirroring: 0,
+            irq_control: 0,
+            irq_counter: 0,
+            is_irq: false,
+            audio: Default::default(),
+        };
+    }
+
+    fn low_bank_read(&mut self, address: usize) -> u8 {
+        let offset = address - RAM_BEGIN;
+        if self.ram_control & RAM_SELECT == 0 {
+            let bank = (self.ram_control & BANK_MASK) as usize;
+            let index = (bank * PRG_WINDOW_SIZE + offset) % self.prg_rom.len();
+            return self.prg_rom[index];
+        }
+        if self.ram_control & RAM_ENABLE == 0 {
+            return 0;
+        }
+        return self.prg_ram.read(address);
+    }
+
+    fn low_bank_write(&mut self, address: usize, value: u8) {
+        let is_ram_writable =
+            self.ram_control & (RAM_SELECT | RAM_ENABLE) == RAM_SELECT | RAM_ENABLE;
+        if is_ram_writable {
+            self.prg_ram.write(address, value);
+        }
+    }
+
+    fn parameter_write(&mut self, value: u8) {
+        match self.command {
+            0..=COMMAND_CHR_END => self.chr_banks.set(self.command as usize, value as usize),
+            COMMAND_PRG_RAM => self.ram_control = value,
+            COMMAND_PRG_BEGIN..=COMMAND_PRG_END => {
+                let window = (self.command - COMMAND_PRG_BEGIN) as usize;
+                self.prg_banks.set(window, (value & BANK_MASK) as usize);
+            }
+            COMMAND_MIRRORING => self.mirroring = value & 0x03,
+            COMMAND_IRQ_CONTROL => {
+                self.irq_control = value;
+                self.is_irq = false;
+            }
+            COMMAND_IRQ_LOW => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+            COMMAND_IRQ_HIGH => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fme7() -> SunsoftFme7 {
+        let prg_rom = (0..0x10000)
+            .map(|index| (index / PRG_WINDOW_SIZE) as u8)
+            .collect();
+        return SunsoftFme7::new(
+            prg_rom,
+            Chr::new(vec![], 0),
+            PrgRam::new(PrgRam::SIZE, true),
+        );
+    }
+
+    fn set_ram_control(mapper: &mut SunsoftFme7, value: u8) {
+        mapper.cpu_write(COMMAND_BEGIN, COMMAND_PRG_RAM);
+        mapper.cpu_write(PARAMETER_BEGIN, value);
+    }
+
+    #[test]
+    fn ram_needs_select_and_enable() {
+        let mut mapper = fme7();
+        set_ram_control(&mut mapper, RAM_SELECT);
+        mapper.cpu_write(RAM_BEGIN, 0x12);
+        assert_eq!(mapper.cpu_read(RAM_BEGIN), 0);
+        assert!(!mapper.poll_save_dirty());
+
+        set_ram_control(&mut mapper, RAM_SELECT | RAM_ENABLE);
+        assert_eq!(mapper.cpu_read(RAM_BEGIN), 0);
+        mapper.cpu_write(RAM_BEGIN, 0x12);
+        assert_eq!(mapper.cpu_read(RAM_BEGIN), 0x12);
+        assert!(mapper.poll_save_dirty());
+    }
+
+    #[test]
+    fn rom_bank_without_select() {
+        let mut mapper = fme7();
+        set_ram_control(&mut mapper, RAM_ENABLE | 3);
+        mapper.cpu_write(RAM_BEGIN, 0x12);
+        assert_eq!(mapper.cpu_read(RAM_BEGIN), 3);
+        assert!(!mapper.poll_save_dirty());
+        set_ram_control(&mut mapper, RAM_SELECT | RAM_ENABLE);
+        assert_eq!(mapper.cpu_read(RAM_BEGIN), 0);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::nes::cart::{
+    banks::Banks,
+    chr::Chr,
+    flash::Flash,
+    header::{ControlOne, Header},
+};
+use crate::nes::{memory::Memory, ppu::VramMirror};
+
+use super::Mapper;
+
+const PRG_WINDOW_SIZE: usize = 0x4000;
+const NUM_PRG_WINDOWS: usize = 2;
+const CHR_WINDOW_SIZE: usize = 0x2000;
+const FOUR_SCREEN_OFFSET: usize = 0x6000;
+const NAMETABLE_MASK: usize = 0x1FFF;
+
+const PRG_BEGIN: usize = 0x8000;
+const FLASH_END: usize = 0xBFFF;
+
+const PRG_BANK_MASK: u8 = 0x1F;
+const CHR_BANK_MASK: u8 = 0x60;
+const CHR_BANK_SHIFT: u8 = 5;
+const SCREEN_SELECT: u8 = 0x80;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Unrom512Mirroring {
+    Horizontal,
+    Vertical,
+    OneScreen,
+    FourScreen,
+}
+
+/// RetroUSB UNROM-512 (mapper 30). The flashable variant has no bus conflicts
+/// and lets the game rewrite its own PRG flash to store saves.
+pub struct Unrom512 {
+    pub prg_rom: Vec<u8>,
+    pub chr: Chr,
+    pub prg_banks: Banks,
+    pub chr_banks: Banks,
+    pub mirroring: Unrom512Mirroring,
+    pub screen: u8,
+    pub is_flashable: bool,
+    pub flash: Flash,
+}
+
+impl Mapper for Unrom512 {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        if address < PRG_BEGIN {
+            return 0;
+        }
+        let index = self.prg_banks.map(address - PRG_BEGIN);
+        return self.flash.read(&self.prg_rom, index);
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        if address < PRG_BEGIN {
+            return;
+        }
+        if !self.is_flashable {
+            let value = value & self.cpu_read(address);
+            self.bank_write(value);
+        } else if address <= FLASH_END {
+            let index = self.prg_banks.map(address - PRG_BEGIN);
+            self.flash.write(&mut self.prg_rom, index, value);
+        } else {
+            self.bank_write(value);
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        let index = self.chr_banks.map(address);
+        return self.chr.read(index);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        let index = self.chr_banks.map(address);
+        self.chr.write(index, value);
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn mirroring(&self) -> Option<VramMirror> {
+        let result = match self.mirroring {
+            Unrom512Mirroring::Horizontal => VramMirror::Horizontal,
+            Unrom512Mirroring::Vertical => VramMirror::Vertical,
+            Unrom512Mirroring::OneScreen if self.screen == 0 => VramMirror::SingleScreenA,
+            Unrom512Mirroring::OneScreen => VramMirror::SingleScreenB,
+            Unrom512Mirroring::FourScreen => return None,
+        };
+        return Some(result);
+    }
+
+    fn nametable_read(&mut self, address: usize) -> Option<u8> {
+        if self.mirroring != Unrom512Mirroring::FourScreen {
+            return None;
+        }
+        let index = FOUR_SCREEN_OFFSET + (address & NAMETABLE_MASK);
+        return Some(self.chr.read(index));
+    }
+
+    fn nametable_write(&mut self, address: usize, value: u8) -> bool {
+        if self.mirroring != Unrom512Mirroring::FourScreen {
+            return false;
+        }
+        let index = FOUR_SCREEN_OFFSET + (address & NAMETABLE_MASK);
+        self.chr.write(index, value);
+        return true;
+    }
+
+    fn save_data(&mut self) -> Option<&mut [u8]> {
+        if !self.is_flashable {
+            return None;
+        }
+        return Some(&mut self.prg_rom);
+    }
+
+    fn poll_save_dirty(&mut self) -> bool {
+        let result = self.flash.is_dirty;
+        self.flash.is_dirty = false;
+        return result;
+    }
+}
+
+impl Unrom512 {
+    pub const NUMBER: u8 = 30;
+    pub const CHR_RAM_SIZE: usize = 0x8000;
+
+    pub fn new(header: &Header, prg_rom: Vec<u8>, chr: Chr) -> Self {
+        let control = header.control.control_one;
+        let is_vertical = control.contains(ControlOne::Mirroring);
+        let is_alternate = control.contains(ControlOne::VramLayout);
+        let mirroring = match (is_alternate, is_vertical) {
+            (false, false) => Unrom512Mirroring::Horizontal,
+            (false, true) => Unrom512Mirroring::Vertical,
+            (true, false) => Unrom512Mirroring::OneScreen,
+            (true, true) => Unrom512Mirroring::FourScreen,
+        };
+        let mut prg_banks = Banks::new(prg_rom.len(), PRG_WINDOW_SIZE, NUM_PRG_WINDOWS);
+        prg_banks.set(0, 0);
+        prg_banks.set_last(1);
+        return Self {
+            chr_banks: Banks::new(chr.len(), CHR_WINDOW_SIZE, 1),
+            prg_rom: prg_rom,
+            chr: chr,
+            prg_banks: prg_banks,
+            mirroring: mirroring,
+            screen: 0,
+            is_flashable: control.contains(ControlOne::Ram),
+            flash: Default::default(),
+        };
+    }
+
+    fn bank_write(&mut self, value: u8) {
+        self.prg_banks.set(0, (value & PRG_BANK_MASK) as usize);
+        let chr_bank = (value & CHR_BANK_MASK) >> CHR_BANK_SHIFT;
+        self.chr_banks.set(0, chr_bank as usize);
+        self.screen = if value & SCREEN_SELECT != 0 { 1 } else { 0 };
+    }
+}
@@ -0,0 +1,94 @@
+use crate::nes::{
+    cart::{chr::Chr, prg_ram::PrgRam},
+    memory::Memory,
+};
+
+use super::Mapper;
+
+const PRG_BEGIN: usize = 0x8000;
+const PRG_SWITCH_END: usize = 0x9FFF;
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x2000;
+const STANDARD_PRG_SIZE: usize = 0x8000;
+const CONTROLLER_PORT: usize = 0x4016;
+const BANK_SELECT: u8 = 0b100;
+
+/// The Vs. UniSystem's own board. Bit 2 of writes to $4016 picks the 8 KiB
+/// CHR bank and, on the 40 KiB games, the PRG bank at $8000.
+pub struct VsUnisystem {
+    pub prg_rom: Vec<u8>,
+    pub chr: Chr,
+    pub prg_ram: PrgRam,
+    pub bank: usize,
+}
+
+impl Default for VsUnisystem {
+    fn default() -> Self {
+        Self {
+            prg_rom: Default::default(),
+            chr: Default::default(),
+            prg_ram: Default::default(),
+            bank: 0,
+        }
+    }
+}
+
+impl Mapper for VsUnisystem {
+    fn cpu_read(&mut self, address: usize) -> u8 {
+        let is_extra_bank = self.prg_rom.len() > STANDARD_PRG_SIZE;
+        let result = match address {
+            PrgRam::BEGIN..=PrgRam::END => self.prg_ram.read(address),
+            PRG_BEGIN..=PRG_SWITCH_END if is_extra_bank => {
+                let bank_base = self.bank * STANDARD_PRG_SIZE;
+                self.prg_rom[bank_base + address - PRG_BEGIN]
+            }
+            PRG_BEGIN..=0xFFFF => {
+                let address = (address - PRG_BEGIN) % self.prg_rom.len();
+                self.prg_rom[address]
+            }
+            _ => 0,
+        };
+        return result;
+    }
+
+    fn cpu_write(&mut self, address: usize, value: u8) {
+        match address {
+            CONTROLLER_PORT => self.bank = ((value & BANK_SELECT) >> 2) as usize,
+            PrgRam::BEGIN..=PrgRam::END => self.prg_ram.write(address, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: usize) -> u8 {
+        return self.chr.read(self.bank * CHR_BANK_SIZE + address);
+    }
+
+    fn ppu_write(&mut self, address: usize, value: u8) {
+        self.chr.write(self.bank * CHR_BANK_SIZE + address, value);
+    }
+
+    fn chr_ram(&mut self) -> &mut [u8] {
+        return self.chr.ram();
+    }
+
+    fn save_data(&mut self) -> Option<&mut [u8]> {
+        return self.prg_ram.save_data();
+    }
+
+    fn poll_save_dirty(&mut self) -> bool {
+        return self.prg_ram.poll_dirty();
+    }
+}
+
+impl VsUnisystem {
+    pub const NUMBER: u8 = 99;
+
+    pub fn new(prg_rom: Vec<u8>, chr: Chr, prg_ram: PrgRam) -> Self {
+        return Self {
+            prg_rom: prg_rom,
+            chr: chr,
+            prg_ram: prg_ram,
+            bank: 0,
+        };
+    }
+}
@@ -0,0 +1,339 @@
+use bitmask_enum::bitmask;
+
+use super::{header::Region, load_error::LoadError};
+
+const NSF_MAGIC: &[u8] = b"NESM\x1A";
+const NSFE_MAGIC: &[u8] = b"NSFE";
+const NSF_HEADER_SIZE: usize = 0x80;
+const NUM_BANKS: usize = 8;
+const STRING_SIZE: usize = 32;
+
+const TOTAL_SONGS: usize = 0x06;
+const STARTING_SONG: usize = 0x07;
+const LOAD_ADDRESS: usize = 0x08;
+const INIT_ADDRESS: usize = 0x0A;
+const PLAY_ADDRESS: usize = 0x0C;
+const TITLE: usize = 0x0E;
+const ARTIST: usize = 0x2E;
+const COPYRIGHT: usize = 0x4E;
+const NTSC_SPEED: usize = 0x6E;
+const BANKS: usize = 0x70;
+const PAL_SPEED: usize = 0x78;
+const REGION: usize = 0x7A;
+const EXPANSION: usize = 0x7B;
+
+const NSFE_INFO_SIZE: usize = 9;
+const REGION_PAL: u8 = 0x01;
+const REGION_DUAL: u8 = 0x02;
+const DEFAULT_NTSC_SPEED: u16 = 16639;
+const DEFAULT_PAL_SPEED: u16 = 19997;
+
+#[bitmask(u8)]
+pub enum NsfExpansion {
+    Vrc6 = 0x01,
+    Vrc7 = 0x02,
+    Fds = 0x04,
+    Mmc5 = 0x08,
+    Namco163 = 0x10,
+    Sunsoft5b = 0x20,
+}
+
+/// A parsed NSF or NSFe rip. Tracks are numbered from 0 here even though NSF
+/// headers count from 1.
+pub struct Nsf {
+    pub num_tracks: usize,
+    pub starting_track: usize,
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    pub title: String,
+    pub artist: String,
+    pub copyright: String,
+    pub ntsc_speed: u16,
+    pub pal_speed: u16,
+    pub banks: [u8; NUM_BANKS],
+    pub region: Region,
+    pub expansion: NsfExpansion,
+    pub data: Vec<u8>,
+    pub track_titles: Vec<String>,
+    pub track_lengths: Vec<Option<u32>>,
+}
+
+pub fn is_nsf(data: &[u8]) -> bool {
+    return data.starts_with(NSF_MAGIC) || data.starts_with(NSFE_MAGIC);
+}
+
+pub fn is_nsfe(data: &[u8]) -> bool {
+    return data.starts_with(NSFE_MAGIC);
+}
+
+impl Nsf {
+    pub fn new(data: &[u8]) -> Result<Self, LoadError> {
+        if data.starts_with(NSFE_MAGIC) {
+            return Self::from_nsfe(data);
+        }
+        if !data.starts_with(NSF_MAGIC) || data.len() < NSF_HEADER_SIZE {
+            return Err(invalid("missing NSF header"));
+        }
+        let mut banks = [0; NUM_BANKS];
+        banks.copy_from_slice(&data[BANKS..BANKS + NUM_BANKS]);
+        return Ok(Self {
+            num_tracks: data[TOTAL_SONGS] as usize,
+            starting_track: (data[STARTING_SONG] as usize).saturating_sub(1),
+            load_address: read_u16(data, LOAD_ADDRESS),
+            init_address: read_u16(data, INIT_ADDRESS),
+            play_address: read_u16(data, PLAY_ADDRESS),
+            title: read_string(&data[TITLE..TITLE + STRING_SIZE]),
+            artist: read_string(&data[ARTIST..ARTIST + STRING_SIZE]),
+            copyright: read_string(&data[COPYRIGHT..COPYRIGHT + STRING_SIZE]),
+            ntsc_speed: read_u16(data, NTSC_SPEED),
+            pal_speed: read_u16(data, PAL_SPEED),
+            banks: banks,
+            region: region(data[REGION]),
+            expansion: NsfExpansion::from(data[EXPANSION]),
+            data: data[NSF_HEADER_SIZE..].to_vec(),
+            track_titles: vec![],
+            track_lengths: vec![],
+        });
+    }
+
+    /// NSFe stores the same fields in chunks, plus per-track titles and
+    /// lengths. Playback speeds come from RATE when present.
+    fn from_nsfe(data: &[u8]) -> Result<Self, LoadError> {
+        let mut result = Self {
+            num_tracks: 0,
+            starting_track: 0,
+            load_address: 0,
+            init_address: 0,
+            play_address: 0,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ntsc_speed: DEFAULT_NTSC_SPEED,
+            pal_speed: DEFAULT_PAL_SPEED,
+            banks: [0; NUM_BANKS],
+            region: Region::Ntsc,
+            expansion: NsfExpansion::none(),
+            data: vec![],
+            track_titles: vec![],
+            track_lengths: vec![],
+        };
+        let mut has_info = false;
+        let mut offset = NSFE_MAGIC.len();
+        while offset + 8 <= data.len() {
+            let length = u32::from_le_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ]) as usize;
+            let id = &data[offset + 4..offset + 8];
+            let begin = offset + 8;
+            let end = begin + length;
+            if end > data.len() {
+                return Err(invalid("truncated NSFe chunk"));
+            }
+            let chunk = &data[begin..end];
+            match id {
+                b"INFO" if chunk.len() >= NSFE_INFO_SIZE => {
+                    result.load_address = read_u16(chunk, 0);
+                    result.init_address = read_u16(chunk, 2);
+                    result.play_address = read_u16(chunk, 4);
+                    result.region = region(chunk[6]);
+                    result.expansion = NsfExpansion::from(chunk[7]);
+                    result.num_tracks = chunk[8] as usize;
+                    result.starting_track = chunk.get(9).copied().unwrap_or(0) as usize;
+                    has_info = true;
+                }
+                b"DATA" => result.data = chunk.to_vec(),
+                b"BANK" => {
+                    let size = chunk.len().min(NUM_BANKS);
+                    result.banks[..size].copy_from_slice(&chunk[..size]);
+                }
+                b"RATE" => {
+                    if chunk.len() >= 2 {
+                        result.ntsc_speed = read_u16(chunk, 0);
+                    }
+                    if chunk.len() >= 4 {
+                        result.pal_speed = read_u16(chunk, 2);
+                    }
+                }
+                b"auth" => {
+                    let mut strings = chunk.split(|&byte| byte == 0).map(read_string);
+                    result.title = strings.next().unwrap_or_default();
+                    result.artist = strings.next().unwrap_or_default();
+                    result.copyright = strings.next().unwrap_or_default();
+                }
+                b"tlbl" => {
+                    result.track_titles = chunk.split(|&byte| byte == 0).map(read_string).collect();
+                }
+                b"time" => {
+                    result.track_lengths = chunk
+                        .chunks_exact(4)
+                        .map(|time| i32::from_le_bytes([time[0], time[1], time[2], time[3]]))
+                        .map(|time| if time < 0 { None } else { Some(time as u32) })
+                        .collect();
+                }
+                b"NEND" => break,
+                _ => {}
+            }
+            offset = end;
+        }
+        if !has_info || result.data.is_empty() {
+            return Err(invalid("NSFe file is missing INFO or DATA"));
+        }
+        return Ok(result);
+    }
+
+    pub fn is_bankswitched(&self) -> bool {
+        return self.banks.iter().any(|&bank| bank != 0);
+    }
+
+    /// Microseconds between PLAY calls for the region the rip targets.
+    pub fn play_speed(&self) -> u16 {
+        let result = match self.region {
+            Region::Pal | Region::Dendy => self.pal_speed,
+            _ => self.ntsc_speed,
+        };
+        return result;
+    }
+
+    pub fn track_title(&self, track: usize) -> Option<&str> {
+        return self
+            .track_titles
+            .get(track)
+            .filter(|title| !title.is_empty())
+            .map(|title| title.as_str());
+    }
+
+    /// Track length in milliseconds when the NSFe gives one.
+    pub fn track_length(&self, track: usize) -> Option<u32> {
+        return self.track_lengths.get(track).copied().flatten();
+    }
+}
+
+/// Names the expansion chips in a set, for messages.
+pub fn expansion_names(expansion: NsfExpansion) -> Vec<&'static str> {
+    let chips = [
+        (NsfExpansion::Vrc6, "VRC6"),
+        (NsfExpansion::Vrc7, "VRC7"),
+        (NsfExpansion::Fds, "FDS"),
+        (NsfExpansion::Mmc5, "MMC5"),
+        (NsfExpansion::Namco163, "Namco 163"),
+        (NsfExpansion::Sunsoft5b, "Sunsoft 5B"),
+    ];
+    return chips
+        .iter()
+        .filter(|(chip, _)| expansion.contains(*chip))
+        .map(|(_, name)| *name)
+        .collect();
+}
+
+fn region(flags: u8) -> Region {
+    if flags & REGION_DUAL != 0 {
+        return Region::Multi;
+    }
+    if flags & REGION_PAL != 0 {
+        return Region::Pal;
+    }
+    return Region::Ntsc;
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    return u16::from_le_bytes([data[offset], data[offset + 1]]);
+}
+
+fn read_string(data: &[u8]) -> String {
+    let end = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(data.len());
+    return String::from_utf8_lossy(&data[..end]).to_string();
+}
+
+fn invalid(reason: &str) -> LoadError {
+    return LoadError::InvalidFormat(reason.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nsf_header() -> Vec<u8> {
+        let mut result = NSF_MAGIC.to_vec();
+        result.resize(NSF_HEADER_SIZE, 0);
+        result[TOTAL_SONGS] = 3;
+        result[STARTING_SONG] = 2;
+        result[LOAD_ADDRESS..LOAD_ADDRESS + 2].copy_from_slice(&0x8000u16.to_le_bytes());
+        result[INIT_ADDRESS..INIT_ADDRESS + 2].copy_from_slice(&0x8003u16.to_le_bytes());
+        result[PLAY_ADDRESS..PLAY_ADDRESS + 2].copy_from_slice(&0x8006u16.to_le_bytes());
+        result[TITLE..TITLE + 4].copy_from_slice(b"Song");
+        result[NTSC_SPEED..NTSC_SPEED + 2].copy_from_slice(&16639u16.to_le_bytes());
+        result[PAL_SPEED..PAL_SPEED + 2].copy_from_slice(&19997u16.to_le_bytes());
+        result[REGION] = REGION_PAL;
+        result[EXPANSION] = 0x21;
+        result.extend_from_slice(&[0xEA; 16]);
+        return result;
+    }
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut result = (data.len() as u32).to_le_bytes().to_vec();
+        result.extend_from_slice(id);
+        result.extend_from_slice(data);
+        return result;
+    }
+
+    #[test]
+    fn parses_nsf() {
+        let nsf = Nsf::new(&nsf_header()).unwrap();
+        assert_eq!(nsf.num_tracks, 3);
+        assert_eq!(nsf.starting_track, 1);
+        assert_eq!(nsf.load_address, 0x8000);
+        assert_eq!(nsf.init_address, 0x8003);
+        assert_eq!(nsf.play_address, 0x8006);
+        assert_eq!(nsf.title, "Song");
+        assert!(nsf.region == Region::Pal);
+        assert_eq!(nsf.play_speed(), 19997);
+        assert_eq!(nsf.data.len(), 16);
+        assert!(!nsf.is_bankswitched());
+        assert_eq!(expansion_names(nsf.expansion), ["VRC6", "Sunsoft 5B"]);
+    }
+
+    #[test]
+    fn parses_nsfe() {
+        let mut data = NSFE_MAGIC.to_vec();
+        let info = [0x00, 0x80, 0x03, 0x80, 0x06, 0x80, 0x00, 0x04, 2, 1];
+        data.extend(chunk(b"INFO", &info));
+        data.extend(chunk(b"DATA", &[0xEA; 8]));
+        data.extend(chunk(b"BANK", &[0, 1]));
+        data.extend(chunk(b"auth", b"Title\0Artist\0Copyright\0"));
+        data.extend(chunk(b"tlbl", b"One\0Two\0"));
+        let mut times = 90000i32.to_le_bytes().to_vec();
+        times.extend_from_slice(&(-1i32).to_le_bytes());
+        data.extend(chunk(b"time", &times));
+        data.extend(chunk(b"NEND", &[]));
+        let nsf = Nsf::new(&data).unwrap();
+        assert_eq!(nsf.init_address, 0x8003);
+        assert_eq!(nsf.num_tracks, 2);
+        assert_eq!(nsf.starting_track, 1);
+        assert!(nsf.expansion == NsfExpansion::Fds);
+        assert!(nsf.is_bankswitched());
+        assert_eq!(nsf.artist, "Artist");
+        assert_eq!(nsf.track_title(1), Some("Two"));
+        assert_eq!(nsf.track_length(0), Some(90000));
+        assert_eq!(nsf.track_length(1), None);
+        assert_eq!(nsf.play_speed(), DEFAULT_NTSC_SPEED);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(Nsf::new(&nsf_header()[..NSF_HEADER_SIZE - 1]).is_err());
+        let mut truncated = NSFE_MAGIC.to_vec();
+        truncated.extend(chunk(b"DATA", &[0; 8]));
+        truncated.pop();
+        assert!(Nsf::new(&truncated).is_err());
+        let mut no_info = NSFE_MAGIC.to_vec();
+        no_info.extend(chunk(b"DATA", &[0; 8]));
+        assert!(Nsf::new(&no_info).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use super::{ips, load_error::LoadError};
+
+const BPS_MAGIC: &[u8] = b"BPS1";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const FOOTER_SIZE: usize = 12;
+
+const BPS_SOURCE_READ: usize = 0;
+const BPS_TARGET_READ: usize = 1;
+const BPS_SOURCE_COPY: usize = 2;
+const BPS_TARGET_COPY: usize = 3;
+
+/// Applies an IPS, BPS or UPS patch to a raw ROM image, picking the format
+/// from the patch header.
+pub fn apply(data: &[u8], patch: &[u8]) -> Result<Vec<u8>, LoadError> {
+    if ips::is_ips(patch) {
+        return ips::apply(data, patch);
+    }
+    if patch.starts_with(BPS_MAGIC) {
+        return apply_bps(data, patch);
+    }
+    if patch.starts_with(UPS_MAGIC) {
+        return apply_ups(data, patch);
+    }
+    return Err(invalid("unknown patch format"));
+}
+
+fn apply_bps(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, LoadError> {
+    let target_crc = check_footer(source, patch)?;
+    let end = patch.len() - FOOTER_SIZE;
+    let mut reader = Reader::new(patch, BPS_MAGIC.len());
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.skip(metadata_size)?;
+    if source.len() != source_size {
+        return Err(invalid("BPS source size mismatch"));
+    }
+    let mut target = vec![0; target_size];
+    let mut output = 0;
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+    while reader.offset < end {
+        let data = reader.number()?;
+        let length = (data >> 2) + 1;
+        if output + length > target_size {
+            return Err(invalid("BPS patch writes past the target"));
+        }
+        match data & 3 {
+            BPS_SOURCE_READ => {
+                let bytes = source.get(output..output + length).ok_or(out_of_range())?;
+                target[output..output + length].copy_from_slice(bytes);
+            }
+            BPS_TARGET_READ => {
+                let bytes = reader.bytes(length)?;
+                target[output..output + length].copy_from_slice(bytes);
+            }
+            BPS_SOURCE_COPY => {
+                source_offset += reader.signed_number()?;
+                let begin = usize::try_from(source_offset).map_err(|_| out_of_range())?;
+                let bytes = source.get(begin..begin + length).ok_or(out_of_range())?;
+                target[output..output + length].copy_from_slice(bytes);
+                source_offset += length as isize;
+            }
+            BPS_TARGET_COPY => {
+                target_offset += reader.signed_number()?;
+                let begin = usize::try_from(target_offset).map_err(|_| out_of_range())?;
+                // The copy may overlap the bytes it is writing, so go one at a time.
+                for index in 0..length {
+                    let value = *target.get(begin + index).ok_or(out_of_range())?;
+                    target[output + index] = value;
+                }
+                target_offset += length as isize;
+            }
+            _ => unreachable!(),
+        }
+        output += length;
+    }
+    check_target(&target, target_crc)?;
+    return Ok(target);
+}
+
+fn apply_ups(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, LoadError> {
+    let target_crc = check_footer(source, patch)?;
+    let end = patch.len() - FOOTER_SIZE;
+    let mut reader = Reader::new(patch, UPS_MAGIC.len());
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    if source.len() != source_size {
+        return Err(invalid("UPS source size mismatch"));
+    }
+    let mut target = source.to_vec();
+    target.resize(target_size, 0);
+    let mut output = 0;
+    while reader.offset < end {
+        output += reader.number()?;
+        loop {
+            let value = reader.bytes(1)?[0];
+            if value == 0 {
+                output += 1;
+                break;
+            }
+            if output < target_size {
+                target[output] ^= value;
+            }
+            output += 1;
+        }
+    }
+    check_target(&target, target_crc)?;
+    return Ok(target);
+}
+
+/// Checks the patch's own CRC and the source CRC, returning the target CRC
+/// from the footer.
+fn check_footer(source: &[u8], patch: &[u8]) -> Result<u32, LoadError> {
+    if patch.len() < FOOTER_SIZE + 4 {
+        return Err(invalid("patch is truncated"));
+    }
+    let footer = &patch[patch.len() - FOOTER_SIZE..];
+    let source_crc = read_u32(&footer[0..4]);
+    let target_crc = read_u32(&footer[4..8]);
+    let patch_crc = read_u32(&footer[8..12]);
+    if crc32fast::hash(&patch[..patch.len() - 4]) != patch_crc {
+        return Err(invalid("patch checksum mismatch"));
+    }
+    if crc32fast::hash(source) != source_crc {
+        return Err(invalid("ROM does not match the patch's source checksum"));
+    }
+    return Ok(target_crc);
+}
+
+fn check_target(target: &[u8], target_crc: u32) -> Result<(), LoadError> {
+    if crc32fast::hash(target) != target_crc {
+        return Err(invalid("patched ROM does not match the target checksum"));
+    }
+    return Ok(());
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    return u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+}
+
+fn invalid(reason: &str) -> LoadError {
+    return LoadError::InvalidPatch(reason.to_string());
+}
+
+fn out_of_range() -> LoadError {
+    return invalid("patch reads outside the ROM");
+}
+
+/// Reads the variable-length numbers BPS and UPS use: seven bits per byte,
+/// least significant first, with the top bit marking the last byte.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self {
+        return Self {
+            data: data,
+            offset: offset,
+        };
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], LoadError> {
+        let result = self
+            .data
+            .get(self.offset..self.offset + length)
+            .ok_or(invalid("patch is truncated"))?;
+        self.offset += length;
+        return Ok(result);
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), LoadError> {
+        self.bytes(length)?;
+        return Ok(());
+    }
+
+    fn number(&mut self) -> Result<usize, LoadError> {
+        let mut result: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let value = self.bytes(1)?[0] as usize;
+            result = result
+                .checked_add((value & 0x7F) * shift)
+                .ok_or(invalid("patch number overflows"))?;
+            if value & 0x80 != 0 {
+                break;
+            }
+            shift = shift
+                .checked_shl(7)
+                .ok_or(invalid("patch number overflows"))?;
+            result += shift;
+        }
+        return Ok(result);
+    }
+
+    fn signed_number(&mut self) -> Result<isize, LoadError> {
+        let value = self.number()?;
+        let magnitude = (value >> 1) as isize;
+        return Ok(if value & 1 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(value: usize) -> Vec<u8> {
+        let mut result = vec![];
+        let mut value = value;
+        loop {
+            let bits = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                result.push(0x80 | bits);
+                return result;
+            }
+            result.push(bits);
+            value -= 1;
+        }
+    }
+
+    fn finish(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+        let patch_crc = crc32fast::hash(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        return patch;
+    }
+
+    fn action(kind: usize, length: usize) -> Vec<u8> {
+        return number(((length - 1) << 2) | kind);
+    }
+
+    const SOURCE: &[u8] = b"Hello, World";
+    const BPS_TARGET: &[u8] = b"Hello, BPSWorldHel";
+
+    fn bps_patch() -> Vec<u8> {
+        let mut patch = BPS_MAGIC.to_vec();
+        patch.extend(number(SOURCE.len()));
+        patch.extend(number(BPS_TARGET.len()));
+        patch.extend(number(0));
+        patch.extend(action(BPS_SOURCE_READ, 7));
+        patch.extend(action(BPS_TARGET_READ, 3));
+        patch.extend_from_slice(b"BPS");
+        patch.extend(action(BPS_SOURCE_COPY, 5));
+        patch.extend(number(7 << 1));
+        patch.extend(action(BPS_TARGET_COPY, 3));
+        patch.extend(number(0));
+        return finish(patch, SOURCE, BPS_TARGET);
+    }
+
+    #[test]
+    fn number_round_trips() {
+        for value in [0, 1, 0x7F, 0x80, 0x3FFF, 0x4000, 0x4080, 0x12345678] {
+            let bytes = number(value);
+            assert_eq!(Reader::new(&bytes, 0).number().unwrap(), value);
+        }
+        let negative = number((5 << 1) | 1);
+        assert_eq!(Reader::new(&negative, 0).signed_number().unwrap(), -5);
+    }
+
+    #[test]
+    fn applies_bps() {
+        assert_eq!(apply(SOURCE, &bps_patch()).unwrap(), BPS_TARGET);
+    }
+
+    #[test]
+    fn bps_checks_crcs() {
+        assert!(apply(b"Hello, world", &bps_patch()).is_err());
+
+        let mut corrupt = bps_patch();
+        corrupt[BPS_MAGIC.len()] ^= 1;
+        assert!(apply(SOURCE, &corrupt).is_err());
+
+        // A well-formed patch whose target CRC is wrong.
+        let mut patch = bps_patch();
+        patch.truncate(patch.len() - FOOTER_SIZE);
+        let wrong_target = finish(patch, SOURCE, b"something else");
+        assert!(apply(SOURCE, &wrong_target).is_err());
+    }
+
+    #[test]
+    fn applies_ups() {
+        let source = b"ABCDEF";
+        let target = b"ABXDEFGH";
+        let mut patch = UPS_MAGIC.to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(target.len()));
+        patch.extend(number(2));
+        patch.extend_from_slice(&[b'C' ^ b'X', 0]);
+        patch.extend(number(2));
+        patch.extend_from_slice(&[b'G', b'H', 0]);
+        let patch = finish(patch, source, target);
+        assert_eq!(apply(source, &patch).unwrap(), target);
+        assert!(apply(b"ABCDEG", &patch).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        assert!(apply(SOURCE, b"XYZ1").is_err());
+        assert!(apply(SOURCE, BPS_MAGIC).is_err());
+    }
+}
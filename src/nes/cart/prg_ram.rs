@@ -0,0 +1,81 @@
+use crate::nes::memory::Memory;
+
+const TRAINER_BEGIN: usize = 0x7000;
+
+/// Work RAM at $6000-$7FFF. When the cartridge has a battery the contents are
+/// tracked for writing back to the save file.
+pub struct PrgRam {
+    pub data: Vec<u8>,
+    pub is_battery: bool,
+    pub is_dirty: bool,
+}
+
+impl Default for PrgRam {
+    fn default() -> Self {
+        Self {
+            data: vec![],
+            is_battery: false,
+            is_dirty: false,
+        }
+    }
+}
+
+impl Memory for PrgRam {
+    fn read(&mut self, address: usize) -> u8 {
+        if self.data.is_empty() {
+            return 0;
+        }
+        let index = self.map_address(address);
+        return self.data[index];
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        if self.data.is_empty() {
+            return;
+        }
+        let index = self.map_address(address);
+        self.data[index] = value;
+        self.is_dirty |= self.is_battery;
+    }
+}
+
+impl PrgRam {
+    pub const BEGIN: usize = 0x6000;
+    pub const END: usize = 0x7FFF;
+    pub const SIZE: usize = 0x2000;
+
+    pub fn new(size: usize, is_battery: bool) -> Self {
+        return Self {
+            data: vec![0; size],
+            is_battery: is_battery,
+            is_dirty: false,
+        };
+    }
+
+    /// Copies an iNES trainer to $7000-$71FF. A board that declares no RAM
+    /// still gets the full 8 KiB window, since the trainer has to live there.
+    pub fn load_trainer(&mut self, trainer: &[u8]) {
+        if self.data.len() < Self::SIZE {
+            self.data.resize(Self::SIZE, 0);
+        }
+        let begin = TRAINER_BEGIN - Self::BEGIN;
+        self.data[begin..begin + trainer.len()].copy_from_slice(trainer);
+    }
+
+    pub fn save_data(&mut self) -> Option<&mut [u8]> {
+        if !self.is_battery || self.data.is_empty() {
+            return None;
+        }
+        return Some(&mut self.data);
+    }
+
+    pub fn poll_dirty(&mut self) -> bool {
+        let result = self.is_dirty;
+        self.is_dirty = false;
+        return result;
+    }
+
+    fn map_address(&self, address: usize) -> usize {
+        return (address - Self::BEGIN) % self.data.len();
+    }
+}
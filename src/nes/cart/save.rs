@@ -0,0 +1,165 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{ips, mapper::Mapper};
+
+const SAVE_EXTENSION: &str = "sav";
+const DIFF_EXTENSION: &str = "diff.ips";
+
+/// The file a cartridge's non-volatile memory is persisted to, kept next to
+/// the ROM it belongs to. Disk images are written back as an IPS diff against
+/// the original so the image itself is never touched.
+pub struct SaveFile {
+    pub path: PathBuf,
+    pub is_diff: bool,
+}
+
+impl SaveFile {
+    pub fn new(rom_path: &str, is_diff: bool) -> Self {
+        let extension = if is_diff {
+            DIFF_EXTENSION
+        } else {
+            SAVE_EXTENSION
+        };
+        return Self {
+            path: Path::new(rom_path).with_extension(extension),
+            is_diff: is_diff,
+        };
+    }
+
+    pub fn load(&self, mapper: &mut dyn Mapper) {
+        let base = mapper.save_base().map(|base| base.to_vec());
+        let Some(data) = mapper.save_data() else {
+            return;
+        };
+        let Ok(contents) = fs::read(&self.path) else {
+            return;
+        };
+        let contents = match base {
+            Some(base) if self.is_diff => match ips::apply(&base, &contents) {
+                Ok(contents) => contents,
+                Err(error) => {
+                    eprintln!("ignoring save file {}: {}", self.path.display(), error);
+                    return;
+                }
+            },
+            _ => contents,
+        };
+        if contents.len() != data.len() {
+            eprintln!(
+                "ignoring save file {}: expected {} bytes, found {}",
+                self.path.display(),
+                data.len(),
+                contents.len()
+            );
+            return;
+        }
+        data.copy_from_slice(&contents);
+        mapper.save_data_loaded();
+    }
+
+    pub fn flush(&self, mapper: &mut dyn Mapper) {
+        if !mapper.poll_save_dirty() {
+            return;
+        }
+        let base = mapper.save_base().map(|base| base.to_vec());
+        let Some(data) = mapper.save_data() else {
+            return;
+        };
+        let contents = match base {
+            Some(base) if self.is_diff => ips::create(&base, data),
+            _ => data.to_vec(),
+        };
+        if let Err(error) = fs::write(&self.path, contents) {
+            eprintln!("failed to write {}: {}", self.path.display(), error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::{cart::prg_ram::PrgRam, memory::Memory};
+
+    /// Just battery-backed PRG-RAM, which is all a save file looks at.
+    struct BatteryRam {
+        prg_ram: PrgRam,
+    }
+
+    impl Mapper for BatteryRam {
+        fn cpu_read(&mut self, address: usize) -> u8 {
+            return self.prg_ram.read(address);
+        }
+
+        fn cpu_write(&mut self, address: usize, value: u8) {
+            self.prg_ram.write(address, value);
+        }
+
+        fn ppu_read(&mut self, _address: usize) -> u8 {
+            return 0;
+        }
+
+        fn ppu_write(&mut self, _address: usize, _value: u8) {}
+
+        fn chr_ram(&mut self) -> &mut [u8] {
+            return &mut [];
+        }
+
+        fn save_data(&mut self) -> Option<&mut [u8]> {
+            return self.prg_ram.save_data();
+        }
+
+        fn poll_save_dirty(&mut self) -> bool {
+            return self.prg_ram.poll_dirty();
+        }
+    }
+
+    fn battery_ram() -> BatteryRam {
+        return BatteryRam {
+            prg_ram: PrgRam::new(PrgRam::SIZE, true),
+        };
+    }
+
+    fn rom_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}.nes", name, std::process::id()));
+        return path.to_string_lossy().to_string();
+    }
+
+    #[test]
+    fn sav_round_trip() {
+        let rom_path = rom_path("sav_round_trip");
+        let save_file = SaveFile::new(&rom_path, false);
+        let mut mapper = battery_ram();
+        mapper.cpu_write(0x6000, 0x12);
+        mapper.cpu_write(0x7FFF, 0x34);
+        save_file.flush(&mut mapper);
+        assert_eq!(fs::read(&save_file.path).unwrap().len(), PrgRam::SIZE);
+
+        let mut mapper = battery_ram();
+        save_file.load(&mut mapper);
+        fs::remove_file(&save_file.path).unwrap();
+        assert_eq!(mapper.cpu_read(0x6000), 0x12);
+        assert_eq!(mapper.cpu_read(0x7FFF), 0x34);
+    }
+
+    #[test]
+    fn flush_skips_clean_ram() {
+        let rom_path = rom_path("flush_skips_clean_ram");
+        let save_file = SaveFile::new(&rom_path, false);
+        save_file.flush(&mut battery_ram());
+        assert!(!save_file.path.exists());
+    }
+
+    #[test]
+    fn load_ignores_wrong_size() {
+        let rom_path = rom_path("load_ignores_wrong_size");
+        let save_file = SaveFile::new(&rom_path, false);
+        fs::write(&save_file.path, [0xFF; 16]).unwrap();
+        let mut mapper = battery_ram();
+        save_file.load(&mut mapper);
+        fs::remove_file(&save_file.path).unwrap();
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::nes::ppu::VramMirror;
+
+use super::{
+    header::{Header, Region},
+    load_error::LoadError,
+};
+
+const MAGIC: &[u8] = b"UNIF";
+const HEADER_SIZE: usize = 0x20;
+const CHUNK_HEADER_SIZE: usize = 8;
+const INES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x2000;
+const PRG_RAM_SIZE: usize = 0x2000;
+const CHR_RAM_SIZE: usize = 0x2000;
+const NUM_ROM_CHUNKS: usize = 16;
+
+const BOARD_PREFIXES: [&str; 5] = ["NES-", "HVC-", "UNL-", "BTL-", "BMC-"];
+
+/// A UNIF file taken apart into the same pieces an iNES file gives us. Board
+/// names are translated into mapper numbers so the header can be filled in.
+pub struct Unif {
+    pub header: Header,
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    /// The initial contents of CHR-RAM, from the CHR chunks of a VROR file.
+    pub chr_ram: Vec<u8>,
+    pub board: String,
+    pub title: Option<String>,
+}
+
+pub fn is_unif(data: &[u8]) -> bool {
+    return data.starts_with(MAGIC);
+}
+
+impl Unif {
+    pub fn new(data: &[u8]) -> Result<Self, LoadError> {
+        if !is_unif(data) || data.len() < HEADER_SIZE {
+            return Err(LoadError::InvalidFormat("missing UNIF header".to_string()));
+        }
+        let mut board = None;
+        let mut title = None;
+        let mut prg_chunks: [Vec<u8>; NUM_ROM_CHUNKS] = Default::default();
+        let mut chr_chunks: [Vec<u8>; NUM_ROM_CHUNKS] = Default::default();
+        let mut mirroring = None;
+        let mut has_battery = false;
+        let mut is_chr_ram = false;
+        let mut region = Region::Ntsc;
+
+        let mut offset = HEADER_SIZE;
+        while offset + CHUNK_HEADER_SIZE <= data.len() {
+            let id = &data[offset..offset + 4];
+            let length = u32::from_le_bytes([
+                data[offset + 4],
+                data[offset + 5],
+                data[offset + 6],
+                data[offset + 7],
+            ]) as usize;
+            let begin = offset + CHUNK_HEADER_SIZE;
+            let end = begin + length;
+            if end > data.len() {
+                return Err(LoadError::InvalidFormat("truncated UNIF chunk".to_string()));
+            }
+            let chunk = &data[begin..end];
+            match id {
+                b"MAPR" => board = Some(read_string(chunk)),
+                b"NAME" => title = Some(read_string(chunk)),
+                b"MIRR" if !chunk.is_empty() => mirroring = Some(chunk[0]),
+                b"BATR" => has_battery = true,
+                b"VROR" => is_chr_ram = true,
+                b"TVCI" if !chunk.is_empty() => {
+                    region = match chunk[0] {
+                        0 => Region::Ntsc,
+                        1 => Region::Pal,
+                        _ => Region::Multi,
+                    }
+                }
+                _ if id.starts_with(b"PRG") => {
+                    if let Some(index) = chunk_index(id[3]) {
+                        prg_chunks[index] = chunk.to_vec();
+                    }
+                }
+                _ if id.starts_with(b"CHR") => {
+                    if let Some(index) = chunk_index(id[3]) {
+                        chr_chunks[index] = chunk.to_vec();
+                    }
+                }
+                _ => {}
+            }
+            offset = end;
+        }
+
+        let board = board.ok_or(LoadError::InvalidFormat("missing MAPR chunk".to_string()))?;
+        let mapper = board_mapper(&board).ok_or(LoadError::UnsupportedBoard(board.clone()))?;
+        let prg_rom = prg_chunks.concat();
+        // VROR marks the CHR chunks as the initial contents of CHR-RAM.
+        let (chr_rom, chr_ram) = if is_chr_ram {
+            (vec![], chr_chunks.concat())
+        } else {
+            (chr_chunks.concat(), vec![])
+        };
+        if prg_rom.is_empty() {
+            return Err(LoadError::InvalidFormat("missing PRG chunks".to_string()));
+        }
+
+        let mut header = Header::default();
+        header.format_tag = INES_TAG;
+        header.num_prg_banks = num_banks(&prg_rom, PRG_BANK_SIZE, "PRG")?;
+        header.num_chr_banks = num_banks(&chr_rom, CHR_BANK_SIZE, "CHR")?;
+        header.set_mapper(mapper, 0);
+        header.set_battery(has_battery);
+        header.set_mirroring(match mirroring {
+            Some(1) => VramMirror::Vertical,
+            Some(2) => VramMirror::SingleScreenA,
+            Some(3) => VramMirror::SingleScreenB,
+            Some(4) => VramMirror::FourScreen,
+            // 5 leaves mirroring to the mapper, which overrides the header.
+            _ => VramMirror::Horizontal,
+        });
+        let chr_ram_size = if !chr_rom.is_empty() {
+            0
+        } else {
+            chr_ram.len().max(CHR_RAM_SIZE)
+        };
+        header.set_ram_sizes(PRG_RAM_SIZE, chr_ram_size);
+        header.set_region(region);
+        return Ok(Self {
+            header: header,
+            prg_rom: prg_rom,
+            chr_rom: chr_rom,
+            chr_ram: chr_ram,
+            board: board,
+            title: title,
+        });
+    }
+}
+
+/// The iNES bank count for a ROM, which has to be a whole number of banks
+/// that fits in the header's one byte.
+fn num_banks(rom: &[u8], bank_size: usize, name: &str) -> Result<u8, LoadError> {
+    if rom.len() % bank_size != 0 {
+        let reason = format!(
+            "{} size {} is not a multiple of {}",
+            name,
+            rom.len(),
+            bank_size
+        );
+        return Err(LoadError::InvalidFormat(reason));
+    }
+    let result = u8::try_from(rom.len() / bank_size);
+    return result.map_err(|_| LoadError::InvalidFormat(format!("{} too large", name)));
+}
+
+fn read_string(chunk: &[u8]) -> String {
+    let end = chunk
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(chunk.len());
+    return String::from_utf8_lossy(&chunk[..end]).to_string();
+}
+
+fn chunk_index(digit: u8) -> Option<usize> {
+    return (digit as char)
+        .to_digit(NUM_ROM_CHUNKS as u32)
+        .map(|index| index as usize);
+}
+
+/// Maps a UNIF board name onto one of our mapper numbers. Manufacturer
+/// prefixes are dropped since the same board turns up under several of them.
+fn board_mapper(board: &str) -> Option<u8> {
+    let mut name = board.to_uppercase();
+    for prefix in BOARD_PREFIXES {
+        if let Some(stripped) = name.strip_prefix(prefix) {
+            name = stripped.to_string();
+        }
+    }
+    let result = match name.as_str() {
+        "NROM" | "NROM-128" | "NROM-256" | "RROM" | "RROM-128" => 0,
+        "BANDAI-FCG-1" | "BANDAI-FCG-2" | "BANDAI-LZ93D50+24C02" => 16,
+        "ACTION53" | "ACTION-53" => 28,
+        "UNROM-512-8" | "UNROM-512-16" | "UNROM-512-32" => 30,
+        "JLROM" | "JSROM" | "BTR" | "SUNSOFT-FME-7" => 69,
+        "GTROM" | "CHEAPOCABRA" => 111,
+        "BANDAI-FJUMP2" => 153,
+        "BANDAI-LZ93D50+24C01" => 159,
+        _ => return None,
+    };
+    return Some(result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::cart::Cart;
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut result = id.to_vec();
+        result.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        result.extend_from_slice(data);
+        return result;
+    }
+
+    fn unif(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut result = MAGIC.to_vec();
+        result.resize(HEADER_SIZE, 0);
+        for chunk in chunks {
+            result.extend_from_slice(chunk);
+        }
+        return result;
+    }
+
+    #[test]
+    fn builds_header() {
+        let data = unif(&[
+            chunk(b"MAPR", b"NES-NROM-256\0"),
+            chunk(b"NAME", b"Test\0"),
+            chunk(b"PRG0", &[1; PRG_BANK_SIZE * 2]),
+            chunk(b"CHR0", &[2; CHR_BANK_SIZE]),
+            chunk(b"MIRR", &[1]),
+            chunk(b"BATR", &[0]),
+        ]);
+        let unif = Unif::new(&data).unwrap();
+        assert_eq!(unif.header.num_prg_banks, 2);
+        assert_eq!(unif.header.num_chr_banks, 1);
+        assert_eq!(unif.header.control.get_mapper(), 0);
+        assert!(unif.header.mirroring() == VramMirror::Vertical);
+        assert!(unif.header.has_battery());
+        assert_eq!(unif.header.chr_ram_size(), 0);
+        assert_eq!(unif.title.as_deref(), Some("Test"));
+        assert_eq!(unif.prg_rom.len(), PRG_BANK_SIZE * 2);
+    }
+
+    #[test]
+    fn vror_seeds_chr_ram() {
+        let data = unif(&[
+            chunk(b"MAPR", b"NROM\0"),
+            chunk(b"PRG0", &[0; PRG_BANK_SIZE]),
+            chunk(b"CHR0", &[3; CHR_BANK_SIZE]),
+            chunk(b"VROR", &[]),
+        ]);
+        let unif = Unif::new(&data).unwrap();
+        assert!(unif.chr_rom.is_empty());
+        assert_eq!(unif.chr_ram, vec![3; CHR_BANK_SIZE]);
+        assert_eq!(unif.header.num_chr_banks, 0);
+        assert_eq!(unif.header.chr_ram_size(), CHR_RAM_SIZE);
+        let cart = Cart::from_unif(&data).unwrap();
+        assert_eq!(cart.chr_ram(), vec![3; CHR_BANK_SIZE]);
+    }
+
+    #[test]
+    fn single_screen_mirroring() {
+        for (value, mirroring) in [
+            (2, VramMirror::SingleScreenA),
+            (3, VramMirror::SingleScreenB),
+        ] {
+            let data = unif(&[
+                chunk(b"MAPR", b"NROM\0"),
+                chunk(b"PRG0", &[0; PRG_BANK_SIZE]),
+                chunk(b"MIRR", &[value]),
+            ]);
+            assert!(Unif::new(&data).unwrap().header.mirroring() == mirroring);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let partial_bank = unif(&[
+            chunk(b"MAPR", b"NROM\0"),
+            chunk(b"PRG0", &[0; PRG_BANK_SIZE / 2]),
+        ]);
+        assert!(Unif::new(&partial_bank).is_err());
+        let no_prg = unif(&[chunk(b"MAPR", b"NROM\0")]);
+        assert!(Unif::new(&no_prg).is_err());
+        let unknown_board = unif(&[
+            chunk(b"MAPR", b"NES-XYZROM\0"),
+            chunk(b"PRG0", &[0; PRG_BANK_SIZE]),
+        ]);
+        assert!(matches!(
+            Unif::new(&unknown_board),
+            Err(LoadError::UnsupportedBoard(_))
+        ));
+        let mut truncated = unif(&[chunk(b"PRG0", &[0; 16])]);
+        truncated.truncate(truncated.len() - 1);
+        assert!(Unif::new(&truncated).is_err());
+    }
+}
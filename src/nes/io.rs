@@ -0,0 +1,46 @@
+use super::{cart::Cart, memory::Memory, vs_system::VsSystem};
+
+/// APU and controller port registers at $4000-$401F, minus OAM DMA. Neither
+/// is emulated yet, so apart from the Vs. System's extra inputs writes are
+/// dropped and reads come back as 0.
+pub struct Io {
+    pub vs_system: Option<VsSystem>,
+}
+
+impl Default for Io {
+    fn default() -> Self {
+        Self { vs_system: None }
+    }
+}
+
+impl Memory for Io {
+    fn read(&mut self, address: usize) -> u8 {
+        return match &mut self.vs_system {
+            Some(vs_system) => vs_system.read(address),
+            None => 0,
+        };
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        if let Some(vs_system) = &mut self.vs_system {
+            vs_system.write(address, value);
+        }
+    }
+}
+
+impl Io {
+    pub const BEGIN: usize = 0x4000;
+    pub const END: usize = 0x401F;
+
+    pub fn new(cart: &Cart) -> Self {
+        return Self {
+            vs_system: VsSystem::new(cart),
+        };
+    }
+
+    pub fn tick(&mut self, n_cycles: i64) {
+        if let Some(vs_system) = &mut self.vs_system {
+            vs_system.tick(n_cycles);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use super::{cart::Cart, Nes};
+
+/// How long a track plays when its NSFe length doesn't say.
+const DEFAULT_TRACK_SECONDS: f64 = 150.0;
+const MILLISECONDS_PER_SECOND: f64 = 1000.0;
+
+/// Drives an NSF cart: selects tracks, restarts the console for each one and
+/// moves on when a track's NSFe length runs out. Tracks without one get a
+/// fixed length. Moving on when a track goes quiet needs the 2A03's own
+/// channels to listen to, so it waits for an APU.
+pub struct Player {
+    pub nes: Nes,
+    pub track: usize,
+    pub num_tracks: usize,
+    pub track_cycles: i64,
+    pub is_auto_advance: bool,
+}
+
+impl Player {
+    pub fn new(cart: Cart) -> Self {
+        let (track, num_tracks) = match &cart.nsf {
+            Some(nsf) => (nsf.starting_track, nsf.num_tracks.max(1)),
+            None => (0, 1),
+        };
+        let mut result = Self {
+            nes: Nes::new(cart),
+            track: track,
+            num_tracks: num_tracks,
+            track_cycles: 0,
+            is_auto_advance: true,
+        };
+        result.play(track);
+        return result;
+    }
+
+    pub fn play(&mut self, track: usize) {
+        self.track = track % self.num_tracks;
+        self.nes.bus.cart.select_track(self.track);
+        self.nes.reset();
+        self.track_cycles = 0;
+    }
+
+    pub fn next_track(&mut self) {
+        self.play(self.track + 1);
+    }
+
+    pub fn previous_track(&mut self) {
+        self.play(self.track + self.num_tracks - 1);
+    }
+
+    pub fn track_title(&self) -> Option<&str> {
+        return self.nes.bus.cart.nsf.as_ref()?.track_title(self.track);
+    }
+
+    pub fn track_length(&self) -> Option<u32> {
+        return self.nes.bus.cart.nsf.as_ref()?.track_length(self.track);
+    }
+
+    pub fn tick(&mut self) {
+        self.nes.tick();
+        self.track_cycles += 1;
+        if self.is_auto_advance && self.is_track_over() {
+            self.next_track();
+        }
+    }
+
+    fn is_track_over(&self) -> bool {
+        let seconds = match self.track_length() {
+            Some(length) => length as f64 / MILLISECONDS_PER_SECOND,
+            None => DEFAULT_TRACK_SECONDS,
+        };
+        return self.track_cycles as f64 >= seconds * self.nes.timing().cpu_frequency;
+    }
+}
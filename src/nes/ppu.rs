@@ -1,39 +1,90 @@
 mod address;
+mod background;
+mod chr_rom;
 mod control;
 mod mask;
+mod model;
+mod palette;
+mod palette_ram;
+mod picture;
+mod sprites;
 mod status;
 mod vram;
 
 use address::AddressRegister;
+use background::Background;
+use chr_rom::ChrRom;
 use control::Control;
 use mask::Mask;
+pub use model::PpuModel;
+pub use palette::Palette;
+use palette_ram::PaletteRam;
+pub use picture::Picture;
+use sprites::Sprites;
 use status::Status;
-use vram::{VRam, VramMirror};
+use vram::VRam;
+pub use vram::VramMirror;
 
-use crate::nes::cart::Cart;
+use crate::nes::{cart::Cart, timing::Timing};
 
 use super::memory::Memory;
 
-pub const CPU_TO_PPU_CYCLE_SCALE: i64 = 3;
-const NUM_SCANLINES: usize = 262;
 const CYCLES_PER_SCANLINE: i64 = 341;
-const SCANLINE_NMI_TRIGGER: usize = 241;
+/// The last dot of the pre-render line, dropped on odd frames while
+/// rendering so the NTSC picture alternates its color phase.
+const SKIPPED_DOT: i64 = CYCLES_PER_SCANLINE - 1;
+const VBLANK_SET_DOT: i64 = 1;
+const NMI_CANCEL_BEGIN: i64 = 2;
+const NMI_CANCEL_END: i64 = 3;
+const VISIBLE_SCANLINES: usize = 240;
+const VISIBLE_DOTS: usize = 256;
+const CHR_BEGIN: usize = 0x0000;
+const CHR_END: usize = 0x1FFF;
+const VRAM_BEGIN: usize = 0x2000;
+const VRAM_END: usize = 0x3EFF;
+/// $3000-$3EFF mirrors the nametables at $2000-$2EFF.
+const NAMETABLE_MIRROR_MASK: usize = 0x2FFF;
+const NAMETABLE_BASE: u16 = 0x2000;
+const ATTRIBUTE_BASE: u16 = 0x23C0;
+const TILE_SIZE: usize = 16;
+const PATTERN_HIGH_OFFSET: usize = 8;
+const SPRITE_CLEAR_END: usize = 64;
+const SPRITE_FETCH_BEGIN: usize = 257;
+const SPRITE_FETCH_END: usize = 320;
+const SPRITE_FETCH_DOTS: usize = 8;
+const LEFT_COLUMN_WIDTH: usize = 8;
+const GREYSCALE_MASK: u8 = 0x30;
+const EMPHASIS_SHIFT: u8 = 5;
+const COLOR_BITS: u16 = 6;
+/// Stand-in colors for the four pattern values in the tile viewers.
+const TILE_COLORS: [u16; 4] = [0x01, 0x23, 0x27, 0x30];
 
 pub struct Ppu {
     pub control: Control,
     pub mask: Mask,
     pub status: Status,
     pub oam_address: u8,
-    pub scroll: u8,
     pub oam_dma: u8,
     pub oam_data: [u8; Self::OAM_DATA_SIZE],
     pub address: AddressRegister,
     pub vram: VRam,
+    pub palette_ram: PaletteRam,
     pub buffer: u8,
-    pub chr_rom: Vec<u8>,
+    pub chr_rom: ChrRom,
     pub clock: i64,
     pub curr_scanline: usize,
     pub is_nmi_interrupt: bool,
+    pub is_nmi_output: bool,
+    pub is_vblank_suppressed: bool,
+    pub is_odd_frame: bool,
+    pub timing: Timing,
+    pub model: PpuModel,
+    pub background: Background,
+    pub sprites: Sprites,
+    pub line: [u16; Picture::WIDTH],
+    pub frame: Picture,
+    pub next_frame: Picture,
+    pub is_frame_ready: bool,
 }
 
 impl Default for Ppu {
@@ -43,16 +94,27 @@ impl Default for Ppu {
             mask: Default::default(),
             status: Default::default(),
             oam_address: Default::default(),
-            scroll: Default::default(),
             oam_dma: Default::default(),
             vram: Default::default(),
+            palette_ram: Default::default(),
             oam_data: [0; Self::OAM_DATA_SIZE],
             address: Default::default(),
             buffer: 0,
-            chr_rom: vec![],
+            chr_rom: Default::default(),
             clock: 0,
             curr_scanline: 0,
             is_nmi_interrupt: false,
+            is_nmi_output: false,
+            is_vblank_suppressed: false,
+            is_odd_frame: false,
+            timing: Default::default(),
+            model: Default::default(),
+            background: Default::default(),
+            sprites: Default::default(),
+            line: [0; Picture::WIDTH],
+            frame: Picture::new(),
+            next_frame: Picture::new(),
+            is_frame_ready: false,
         }
     }
 }
@@ -61,7 +123,7 @@ impl Memory for Ppu {
     fn read(&mut self, address: usize) -> u8 {
         let address = self.map_address(address);
         let result = match address {
-            Self::STATUS_ADDR => self.status.bits(),
+            Self::STATUS_ADDR => self.status_read(),
             Self::OAM_DATA_ADDR => self.oam_read(),
             Self::DATA_ADDR => self.internal_read(),
             _ => panic!("invalid read at {}", address),
@@ -71,12 +133,17 @@ impl Memory for Ppu {
 
     fn write(&mut self, address: usize, value: u8) {
         let address = self.map_address(address);
+        let address = match address {
+            Self::CONTROL_ADDR if self.model.is_control_swapped() => Self::MASK_ADDR,
+            Self::MASK_ADDR if self.model.is_control_swapped() => Self::CONTROL_ADDR,
+            _ => address,
+        };
         match address {
-            Self::CONTROL_ADDR => self.control = Control::from(value),
+            Self::CONTROL_ADDR => self.control_write(value),
             Self::MASK_ADDR => self.mask = Mask::from(value),
             Self::OAM_ADDR_ADDR => self.set_oam_address(value),
             Self::OAM_DATA_ADDR => self.oam_write(value),
-            Self::SCROLL_ADDR => self.scroll_write(value),
+            Self::SCROLL_ADDR => self.address.scroll_write(value),
             Self::ADDR_ADDR => self.address.write(value),
             Self::DATA_ADDR => self.internal_write(value),
             Self::OAM_DMA_ADDR => todo!(),
@@ -88,7 +155,7 @@ impl Memory for Ppu {
 impl Ppu {
     pub const BEGIN: usize = 0x2000;
     pub const END: usize = 0x3FFF;
-    pub const DMA: usize = 0x4017;
+    pub const DMA: usize = 0x4014;
     pub const OAM_DATA_SIZE: usize = 0x100;
 
     pub const CONTROL_ADDR: usize = 0x2000;
@@ -101,22 +168,33 @@ impl Ppu {
     pub const DATA_ADDR: usize = 0x2007;
     pub const OAM_DMA_ADDR: usize = 0x4014;
 
-    pub fn new(cart: &Cart) -> Self {
+    pub fn new(cart: &Cart, timing: Timing) -> Self {
         return Self {
             control: Default::default(),
             mask: Default::default(),
             status: Default::default(),
             oam_address: Default::default(),
-            scroll: Default::default(),
             oam_dma: Default::default(),
             oam_data: [0; Self::OAM_DATA_SIZE],
             address: Default::default(),
-            vram: Default::default(),
+            vram: VRam::new(cart.header.mirroring()),
+            palette_ram: Default::default(),
             buffer: Default::default(),
-            chr_rom: cart.chr_rom.to_vec(),
+            chr_rom: ChrRom::new(cart.mapper.clone()),
             clock: 0,
             curr_scanline: 0,
             is_nmi_interrupt: false,
+            is_nmi_output: false,
+            is_vblank_suppressed: false,
+            is_odd_frame: false,
+            timing: timing,
+            model: PpuModel::new(&cart.header),
+            background: Default::default(),
+            sprites: Default::default(),
+            line: [0; Picture::WIDTH],
+            frame: Picture::new(),
+            next_frame: Picture::new(),
+            is_frame_ready: false,
         };
     }
 
@@ -136,27 +214,282 @@ impl Ppu {
     }
 
     pub fn tick(&mut self, n_cycles: i64) {
-        self.clock += n_cycles;
+        for _ in 0..n_cycles {
+            self.step();
+        }
+    }
+
+    /// The frame finished at the start of the last VBlank, handed out once.
+    pub fn poll_frame(&mut self) -> Option<&Picture> {
+        if !self.is_frame_ready {
+            return None;
+        }
+        self.is_frame_ready = false;
+        return Some(&self.frame);
+    }
+
+    /// Takes the NMI raised since the last call, if any. An NMI raised as
+    /// VBlank starts is held until the dots where a PPUSTATUS read can still
+    /// cancel it have passed.
+    pub fn poll_nmi(&mut self) -> bool {
+        if self.curr_scanline == self.timing.vblank_scanline && self.clock <= NMI_CANCEL_END {
+            return false;
+        }
+        let result = self.is_nmi_interrupt;
+        self.is_nmi_interrupt = false;
+        return result;
+    }
+
+    fn step(&mut self) {
+        if self.clock == VBLANK_SET_DOT {
+            if self.curr_scanline == self.timing.vblank_scanline {
+                self.start_vblank();
+            } else if self.curr_scanline == self.timing.pre_render_scanline() {
+                self.end_vblank();
+            }
+        }
+        self.render_dot();
+        self.clock += 1;
+        if self.curr_scanline == self.timing.pre_render_scanline()
+            && self.clock == SKIPPED_DOT
+            && self.timing.has_odd_frame_skip
+            && self.is_odd_frame
+            && self.is_rendering()
+        {
+            self.clock = CYCLES_PER_SCANLINE;
+        }
         if (self.clock >= CYCLES_PER_SCANLINE) {
-            self.clock -= CYCLES_PER_SCANLINE;
+            self.clock = 0;
             self.curr_scanline += 1;
-            if (self.curr_scanline == SCANLINE_NMI_TRIGGER
-                && self.control.contains(Control::NmiEnable))
-            {
-                self.is_nmi_interrupt = true;
-            }
-            if (self.curr_scanline >= NUM_SCANLINES) {
+            if (self.curr_scanline >= self.timing.num_scanlines) {
                 self.curr_scanline = 0;
-                self.is_nmi_interrupt = false;
+                self.is_odd_frame = !self.is_odd_frame;
             }
         }
     }
 
-    pub fn render_chr_tile(&mut self, bank: usize, tile_n: usize) -> Frame {
-        let mut frame = Frame::new();
+    fn start_vblank(&mut self) {
+        if !self.is_vblank_suppressed {
+            self.status |= Status::VBlank;
+        }
+        self.is_vblank_suppressed = false;
+        std::mem::swap(&mut self.frame, &mut self.next_frame);
+        self.is_frame_ready = true;
+        self.update_nmi();
+    }
+
+    fn end_vblank(&mut self) {
+        self.status &= !(Status::VBlank | Status::SpriteZeroHit | Status::SpriteOverflow);
+        self.update_nmi();
+    }
+
+    /// The NMI line is VBlank ANDed with NmiEnable, and the CPU only reacts
+    /// when it goes high. Toggling NmiEnable during VBlank can therefore
+    /// raise several NMIs in one frame.
+    fn update_nmi(&mut self) {
+        let is_output =
+            self.status.contains(Status::VBlank) && self.control.contains(Control::NmiEnable);
+        if is_output && !self.is_nmi_output {
+            self.is_nmi_interrupt = true;
+        }
+        self.is_nmi_output = is_output;
+    }
+
+    fn render_dot(&mut self) {
+        let dot = self.clock as usize;
+        let scanline = self.curr_scanline;
+        let is_pre_render = scanline == self.timing.pre_render_scanline();
+        let is_visible = scanline < VISIBLE_SCANLINES;
+        if !is_visible && !is_pre_render {
+            return;
+        }
+        if self.is_rendering() {
+            self.render_sprites_dot(dot, scanline);
+            self.render_background_dot(dot, is_pre_render);
+        }
+        if is_visible && (1..=VISIBLE_DOTS).contains(&dot) {
+            self.line[dot - 1] = self.output_pixel(dot - 1);
+            self.check_sprite_zero_hit(dot - 1);
+            if dot == VISIBLE_DOTS {
+                self.output_line(scanline);
+            }
+        }
+    }
+
+    /// Whether PPUMASK has either layer on. With both off the PPU stops
+    /// fetching and leaves v alone, so the CPU can use it freely.
+    fn is_rendering(&self) -> bool {
+        return self
+            .mask
+            .intersects(Mask::RenderBackground | Mask::RenderSprites);
+    }
+
+    fn render_background_dot(&mut self, dot: usize, is_pre_render: bool) {
+        if (2..=257).contains(&dot) || (322..=337).contains(&dot) {
+            self.background.shift();
+            match (dot - 1) % 8 {
+                0 => {
+                    self.background.load();
+                    self.fetch_nametable();
+                }
+                2 => self.fetch_attribute(),
+                4 => self.fetch_pattern(0),
+                6 => self.fetch_pattern(PATTERN_HIGH_OFFSET),
+                7 => self.address.increment_coarse_x(),
+                _ => {}
+            }
+        }
+        match dot {
+            1 | 321 => self.fetch_nametable(),
+            256 => self.address.increment_y(),
+            257 => self.address.copy_horizontal(),
+            338 | 340 => self.fetch_nametable(),
+            280..=304 if is_pre_render => self.address.copy_vertical(),
+            _ => {}
+        }
+    }
+
+    fn render_sprites_dot(&mut self, dot: usize, scanline: usize) {
+        let height = self.sprite_height();
+        match dot {
+            SPRITE_CLEAR_END => self.sprites.clear_secondary(),
+            VISIBLE_DOTS if scanline < VISIBLE_SCANLINES => {
+                if self.sprites.evaluate(&self.oam_data, scanline, height) {
+                    self.status |= Status::SpriteOverflow;
+                }
+            }
+            SPRITE_FETCH_BEGIN..=SPRITE_FETCH_END => {
+                let offset = dot - SPRITE_FETCH_BEGIN;
+                if offset % SPRITE_FETCH_DOTS == SPRITE_FETCH_DOTS - 1 {
+                    self.fetch_sprite(offset / SPRITE_FETCH_DOTS, scanline, height);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn fetch_sprite(&mut self, slot: usize, scanline: usize, height: usize) {
+        let pattern_base = self.control.get_sprite_pattern_address();
+        let address = self
+            .sprites
+            .pattern_address(slot, scanline, height, pattern_base);
+        let pattern_low = self.chr_rom.read(address);
+        let pattern_high = self.chr_rom.read(address + PATTERN_HIGH_OFFSET);
+        self.sprites.load(slot, pattern_low, pattern_high);
+    }
+
+    fn sprite_height(&self) -> usize {
+        return if self.control.contains(Control::SpriteSize) {
+            16
+        } else {
+            8
+        };
+    }
+
+    /// Picks between the background and sprite pixel at x. A sprite behind
+    /// the background still wins over sprites after it, and only shows where
+    /// the background is transparent.
+    fn pixel(&self, x: usize) -> u8 {
+        let is_left_column = x < LEFT_COLUMN_WIDTH;
+        let background = if !self.mask.contains(Mask::RenderBackground)
+            || (is_left_column && !self.mask.contains(Mask::LeftmostBackground))
+        {
+            0
+        } else {
+            self.background.pixel(self.address.fine_x)
+        };
+        if !self.mask.contains(Mask::RenderSprites) {
+            return background;
+        }
+        let Some((sprite, is_behind)) = self.sprites.pixel(x) else {
+            return background;
+        };
+        let is_clipped = is_left_column && !self.mask.contains(Mask::LeftmostSprite);
+        if is_clipped || (is_behind && background != 0) {
+            return background;
+        }
+        return sprite;
+    }
+
+    /// Sprite 0 hits wherever an opaque sprite 0 pixel lands on an opaque
+    /// background pixel, except at x=255 and inside a clipped left column.
+    fn check_sprite_zero_hit(&mut self, x: usize) {
+        let rendering = Mask::RenderBackground | Mask::RenderSprites;
+        let left_column = Mask::LeftmostBackground | Mask::LeftmostSprite;
+        let is_clipped = x < LEFT_COLUMN_WIDTH && !self.mask.contains(left_column);
+        if x == VISIBLE_DOTS - 1 || is_clipped || !self.mask.contains(rendering) {
+            return;
+        }
+        let background = self.background.pixel(self.address.fine_x);
+        if background != 0 && self.sprites.is_zero_opaque(x) {
+            self.status |= Status::SpriteZeroHit;
+        }
+    }
+
+    /// Looks up the pixel's color as it leaves the PPU: a 6-bit color with
+    /// PPUMASK's greyscale applied and its 3 emphasis bits above it.
+    fn output_pixel(&mut self, x: usize) -> u16 {
+        // With rendering off the PPU shows the backdrop, unless v points into
+        // palette RAM, in which case it shows the entry v points at.
+        let v = self.address.read() as usize;
+        let address = if !self.is_rendering() && v >= PaletteRam::BEGIN {
+            v
+        } else {
+            PaletteRam::BEGIN + self.pixel(x) as usize
+        };
+        let mut color = self.palette_ram.read(address);
+        if self.mask.contains(Mask::Greyscale) {
+            color &= GREYSCALE_MASK;
+        }
+        let mut emphasis = (self.mask.bits() >> EMPHASIS_SHIFT) as u16;
+        if self.timing.is_emphasis_swapped {
+            emphasis = (emphasis & !0b011) | (emphasis & 0b001) << 1 | (emphasis & 0b010) >> 1;
+        }
+        return color as u16 | emphasis << COLOR_BITS;
+    }
+
+    fn output_line(&mut self, y: usize) {
+        self.next_frame.write_line(y, &self.line);
+    }
+
+    fn fetch_nametable(&mut self) {
+        let address = NAMETABLE_BASE | (self.address.v & 0x0FFF);
+        self.background.nametable_latch = self.nametable_read(address as usize);
+    }
+
+    fn fetch_attribute(&mut self) {
+        let v = self.address.v;
+        let address = ATTRIBUTE_BASE | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+        let attribute = self.nametable_read(address as usize);
+        let coarse_x = v & 0x1F;
+        let coarse_y = (v >> 5) & 0x1F;
+        let shift = ((coarse_y & 0x02) << 1) | (coarse_x & 0x02);
+        self.background.attribute_latch = (attribute >> shift) & 0x03;
+    }
+
+    fn fetch_pattern(&mut self, plane_offset: usize) {
+        let fine_y = ((self.address.v >> 12) & 0x07) as usize;
+        let tile = self.background.nametable_latch as usize;
+        let address = self.control.get_background_pattern_address()
+            + tile * TILE_SIZE
+            + plane_offset
+            + fine_y;
+        let value = self.chr_rom.read(address);
+        if plane_offset == 0 {
+            self.background.pattern_low_latch = value;
+        } else {
+            self.background.pattern_high_latch = value;
+        }
+    }
+
+    pub fn render_chr_tile(&mut self, bank: usize, tile_n: usize, palette: &Palette) -> Frame {
+        let mut picture = Picture::new();
         let bank = (bank * 0x1000) as usize;
 
-        let tile = &self.chr_rom[(bank + tile_n * 16)..=(bank + tile_n * 16 + 15)];
+        let mut tile = [0; 16];
+        for i in 0..tile.len() {
+            tile[i] = self.chr_rom.read(bank + tile_n * 16 + i);
+        }
 
         for y in 0..=7 {
             let mut upper = tile[y];
@@ -166,121 +499,107 @@ impl Ppu {
                 let value = (1 & upper) << 1 | (1 & lower);
                 upper = upper >> 1;
                 lower = lower >> 1;
-                let rgb = match value {
-                    0 => SYSTEM_PALLETE[0x01],
-                    1 => SYSTEM_PALLETE[0x23],
-                    2 => SYSTEM_PALLETE[0x27],
-                    3 => SYSTEM_PALLETE[0x30],
-                    _ => panic!("can't be"),
-                };
-                frame.write(x, y, rgb)
+                picture.set(x, y, TILE_COLORS[value as usize]);
             }
         }
-        return frame;
+        return palette.convert(&picture, PixelFormat::Rgb24);
     }
 
-    pub fn render_chr_tile_bank(chr_rom: &Vec<u8>, bank: usize) -> Frame {
-        assert!(bank <= 1);
-
-        let mut frame = Frame::new();
-        let mut tile_y = 0;
-        let mut tile_x = 0;
-        let bank = (bank * 0x1000) as usize;
-
-        for tile_n in 0..255 {
-            if tile_n != 0 && tile_n % 20 == 0 {
-                tile_y += 10;
-                tile_x = 0;
-            }
-            let tile = &chr_rom[(bank + tile_n * 16)..=(bank + tile_n * 16 + 15)];
-
-            for y in 0..=7 {
-                let mut upper = tile[y];
-                let mut lower = tile[y + 8];
-
-                for x in (0..=7).rev() {
-                    let value = (1 & upper) << 1 | (1 & lower);
-                    upper = upper >> 1;
-                    lower = lower >> 1;
-                    let rgb = match value {
-                        0 => SYSTEM_PALLETE[0x01],
-                        1 => SYSTEM_PALLETE[0x23],
-                        2 => SYSTEM_PALLETE[0x27],
-                        3 => SYSTEM_PALLETE[0x30],
-                        _ => panic!("can't be"),
-                    };
-                    frame.write(tile_x + x, tile_y + y, rgb)
-                }
-            }
-
-            tile_x += 10;
-        }
-        return frame;
+    pub fn render_chr_tile_bank(chr_rom: &Vec<u8>, bank: usize, palette: &Palette) -> Frame {
+        return palette.convert(&tile_bank_picture(chr_rom, bank), PixelFormat::Rgb24);
     }
 
     fn internal_read(&mut self) -> u8 {
-        let chr_rom = 0..=0x1FFF;
-        let vram = 0x2000..=0x2FFF;
-        let pallet_table = 0x3F00..=0x3FFF;
         let address = self.address.read() as usize;
         self.increment_address();
         let result = match address {
-            chr_rom => self.chr_read(address),
-            vram => self.ram_read(address),
-            pallet_table => todo!(),
+            CHR_BEGIN..=CHR_END => self.chr_read(address),
+            VRAM_BEGIN..=VRAM_END => self.ram_read(address),
+            PaletteRam::BEGIN..=PaletteRam::END => self.palette_read(address),
             _ => panic!("invalid ppu read at internal address 0x{:04X}", address),
         };
         return result;
     }
 
     fn internal_write(&mut self, value: u8) {
-        let chr_rom = 0..=0x1FFF;
-        let vram = 0x2000..=0x2FFF;
-        let pallet_table = 0x3F00..=0x3FFF;
         let address = self.address.read() as usize;
         self.increment_address();
-        let result = match address {
-            chr_rom => self.chr_write(address, value),
-            vram => self.ram_write(address, value),
-            pallet_table => todo!(),
-            _ => panic!("invalid ppu read at internal address 0x{:04X}", address),
-        };
+        match address {
+            CHR_BEGIN..=CHR_END => self.chr_write(address, value),
+            VRAM_BEGIN..=VRAM_END => self.ram_write(address, value),
+            PaletteRam::BEGIN..=PaletteRam::END => self.palette_ram.write(address, value),
+            _ => panic!("invalid ppu write at internal address 0x{:04X}", address),
+        }
+    }
+
+    /// Palette reads skip the read buffer. The buffer is still refilled, from
+    /// the nametable byte underneath the palette.
+    fn palette_read(&mut self, address: usize) -> u8 {
+        self.buffer = self.nametable_read(address);
+        return self.palette_ram.read(address);
     }
 
     fn ram_read(&mut self, address: usize) -> u8 {
         let result = self.buffer;
-        self.buffer = self.vram.read(address);
+        self.buffer = self.nametable_read(address);
         return result;
     }
 
+    fn nametable_read(&mut self, address: usize) -> u8 {
+        let address = address & NAMETABLE_MIRROR_MASK;
+        self.update_mirroring();
+        return match self.chr_rom.nametable_read(address) {
+            Some(value) => value,
+            None => self.vram.read(address),
+        };
+    }
+
     fn ram_write(&mut self, address: usize, value: u8) {
-        let result = self.vram.write(address, value);
+        let address = address & NAMETABLE_MIRROR_MASK;
+        self.update_mirroring();
+        if !self.chr_rom.nametable_write(address, value) {
+            self.vram.write(address, value);
+        }
+    }
+
+    fn update_mirroring(&mut self) {
+        if let Some(mirror) = self.chr_rom.mirroring() {
+            self.vram.mirror = mirror;
+        }
     }
 
     fn chr_read(&mut self, address: usize) -> u8 {
         let result = self.buffer;
-        self.buffer = self.chr_rom[address];
+        self.buffer = self.chr_rom.read(address);
         return result;
     }
 
     fn chr_write(&mut self, address: usize, value: u8) {
-        self.chr_rom[address] = value;
+        self.chr_rom.write(address, value);
     }
 
-    fn scroll_write(&mut self, value: u8) {
-        todo!()
+    /// Reading PPUSTATUS clears VBlank and the write toggle. A read just
+    /// before VBlank starts stops the flag being set at all that frame, and
+    /// one just after it still sees the flag but cancels the NMI.
+    fn status_read(&mut self) -> u8 {
+        if self.curr_scanline == self.timing.vblank_scanline {
+            match self.clock {
+                VBLANK_SET_DOT => self.is_vblank_suppressed = true,
+                NMI_CANCEL_BEGIN..=NMI_CANCEL_END => self.is_nmi_interrupt = false,
+                _ => {}
+            }
+        }
+        let result = self.status.bits() | self.model.status_id();
+        self.status &= !Status::VBlank;
+        self.address.reset_latch();
+        self.update_nmi();
+        return result;
     }
 
     fn control_write(&mut self, value: u8) {
-        let current_control = self.control;
-        let new_control = Control::from(value);
-        self.control = new_control;
-        if (!current_control.contains(Control::NmiEnable)
-            && new_control.contains(Control::NmiEnable)
-            && self.status.contains(Status::VBlank))
-        {
-            self.is_nmi_interrupt = true;
-        }
+        self.control = Control::from(value);
+        self.address.set_nametable(value);
+        self.update_nmi();
     }
 
     fn map_address(&self, address: usize) -> usize {
@@ -363,8 +682,25 @@ pub static SYSTEM_PALLETE: [(u8, u8, u8); 64] = [
     (0x11, 0x11, 0x11),
 ];
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelFormat {
+    Rgb24,
+    Rgba32,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        return match self {
+            Self::Rgb24 => 3,
+            Self::Rgba32 => 4,
+        };
+    }
+}
+
+/// A frame converted to colors, ready to hand to a frontend.
 pub struct Frame {
     pub data: Vec<u8>,
+    pub format: PixelFormat,
 }
 
 impl Frame {
@@ -372,27 +708,46 @@ impl Frame {
     const HEIGH: usize = 240;
 
     pub fn new() -> Self {
+        return Self::with_format(PixelFormat::Rgb24);
+    }
+
+    pub fn with_format(format: PixelFormat) -> Self {
         return Self {
-            data: vec![0; Self::WIDTH * Self::HEIGH * 3],
+            data: vec![0; Self::WIDTH * Self::HEIGH * format.bytes_per_pixel()],
+            format: format,
         };
     }
 
+    /// Bytes per row, as SDL's texture updates want it.
+    pub fn pitch(&self) -> usize {
+        return Self::WIDTH * self.format.bytes_per_pixel();
+    }
+
     pub fn write(&mut self, x: usize, y: usize, color: (u8, u8, u8)) {
         let index = self.map_index(x, y);
         self.data[index] = color.0;
         self.data[index + 1] = color.1;
         self.data[index + 2] = color.2;
+        if self.format == PixelFormat::Rgba32 {
+            self.data[index + 3] = u8::MAX;
+        }
     }
 
     fn map_index(&self, x: usize, y: usize) -> usize {
-        return (x * 3) + (y * 3 * Self::WIDTH);
+        return (x + y * Self::WIDTH) * self.format.bytes_per_pixel();
     }
 }
 
-pub fn show_tile_bank(chr_rom: &Vec<u8>, bank: usize) -> Frame {
+pub fn show_tile_bank(chr_rom: &Vec<u8>, bank: usize, palette: &Palette) -> Frame {
+    return palette.convert(&tile_bank_picture(chr_rom, bank), PixelFormat::Rgb24);
+}
+
+/// Lays out the first 255 tiles of a pattern table 20 to a row, colored
+/// with `TILE_COLORS`.
+fn tile_bank_picture(chr_rom: &Vec<u8>, bank: usize) -> Picture {
     assert!(bank <= 1);
 
-    let mut frame = Frame::new();
+    let mut picture = Picture::new();
     let mut tile_y = 0;
     let mut tile_x = 0;
     let bank = (bank * 0x1000) as usize;
@@ -412,18 +767,322 @@ pub fn show_tile_bank(chr_rom: &Vec<u8>, bank: usize) -> Frame {
                 let value = (1 & upper) << 1 | (1 & lower);
                 upper = upper >> 1;
                 lower = lower >> 1;
-                let rgb = match value {
-                    0 => SYSTEM_PALLETE[0x01],
-                    1 => SYSTEM_PALLETE[0x23],
-                    2 => SYSTEM_PALLETE[0x27],
-                    3 => SYSTEM_PALLETE[0x30],
-                    _ => panic!("can't be"),
-                };
-                frame.write(tile_x + x, tile_y + y, rgb)
+                picture.set(tile_x + x, tile_y + y, TILE_COLORS[value as usize]);
             }
         }
 
         tile_x += 10;
     }
-    frame
+    return picture;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::cart::header::HEADER_SIZE;
+
+    const PRG_BANK_SIZE: usize = 0x4000;
+
+    /// Runs the PPU up to `dot` of the VBlank scanline, polling for NMIs
+    /// after every dot the way the bus does after every CPU cycle.
+    fn run_to(ppu: &mut Ppu, dot: i64, is_nmi: &mut bool) {
+        while ppu.curr_scanline != ppu.timing.vblank_scanline || ppu.clock != dot {
+            ppu.tick(1);
+            *is_nmi |= ppu.poll_nmi();
+        }
+    }
+
+    /// Reads PPUSTATUS at `dot` of the VBlank scanline and reports the
+    /// VBlank flag it saw and whether an NMI fired by the end of the line.
+    fn read_status_at(dot: i64) -> (bool, bool) {
+        let mut ppu = Ppu::default();
+        ppu.write(Ppu::CONTROL_ADDR, Control::NmiEnable.bits());
+        let mut is_nmi = false;
+        run_to(&mut ppu, dot, &mut is_nmi);
+        let status = ppu.read(Ppu::STATUS_ADDR);
+        run_to(&mut ppu, CYCLES_PER_SCANLINE - 1, &mut is_nmi);
+        return (status & Status::VBlank.bits() != 0, is_nmi);
+    }
+
+    #[test]
+    fn status_read_before_vblank_suppresses_flag_and_nmi() {
+        assert_eq!(read_status_at(VBLANK_SET_DOT - 1), (false, true));
+        assert_eq!(read_status_at(VBLANK_SET_DOT), (false, false));
+    }
+
+    #[test]
+    fn status_read_at_vblank_start_cancels_nmi() {
+        for dot in NMI_CANCEL_BEGIN..=NMI_CANCEL_END {
+            assert_eq!(read_status_at(dot), (true, false), "dot {}", dot);
+        }
+    }
+
+    #[test]
+    fn status_read_after_window_keeps_nmi() {
+        assert_eq!(read_status_at(NMI_CANCEL_END + 1), (true, true));
+    }
+
+    /// A PPU on an NROM board with CHR-RAM. Each palette entry holds its own
+    /// index, so rendered pixels read back as palette indices.
+    fn test_ppu() -> Ppu {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 0];
+        rom.resize(HEADER_SIZE + PRG_BANK_SIZE, 0);
+        let cart = Cart::new(&rom).unwrap();
+        let mut ppu = Ppu::new(&cart, Default::default());
+        for index in 0..0x20 {
+            // $3F10, $3F14, $3F18 and $3F1C are the background's entries.
+            if index < 0x10 || index % 4 != 0 {
+                ppu.palette_ram
+                    .write(PaletteRam::BEGIN + index, index as u8);
+            }
+        }
+        return ppu;
+    }
+
+    fn set_address(ppu: &mut Ppu, address: u16) {
+        ppu.write(Ppu::ADDR_ADDR, (address >> 8) as u8);
+        ppu.write(Ppu::ADDR_ADDR, address as u8);
+    }
+
+    fn write_vram(ppu: &mut Ppu, address: u16, data: &[u8]) {
+        set_address(ppu, address);
+        for value in data {
+            ppu.write(Ppu::DATA_ADDR, *value);
+        }
+    }
+
+    /// Fills every row of `tile` with the same two pattern planes.
+    fn write_tile(ppu: &mut Ppu, tile: u16, low: u8, high: u8) {
+        write_vram(
+            ppu,
+            tile * TILE_SIZE as u16,
+            &[[low; 8], [high; 8]].concat(),
+        );
+    }
+
+    /// Renders two frames from scroll position 0 and returns the second,
+    /// since the first starts without the pre-render line's fetches.
+    fn render(ppu: &mut Ppu) -> Vec<u16> {
+        set_address(ppu, 0);
+        let mut num_frames = 0;
+        loop {
+            ppu.tick(1);
+            if let Some(frame) = ppu.poll_frame() {
+                num_frames += 1;
+                if num_frames == 2 {
+                    return frame.data.clone();
+                }
+            }
+        }
+    }
+
+    fn pixel(frame: &[u16], x: usize, y: usize) -> u16 {
+        return frame[x + y * Picture::WIDTH];
+    }
+
+    fn show_all() -> Mask {
+        return Mask::RenderBackground
+            | Mask::RenderSprites
+            | Mask::LeftmostBackground
+            | Mask::LeftmostSprite;
+    }
+
+    #[test]
+    fn background_tiles_take_attribute_quadrant_palettes() {
+        let mut ppu = test_ppu();
+        write_tile(&mut ppu, 1, 0xFF, 0x00);
+        write_tile(&mut ppu, 2, 0x00, 0xFF);
+        write_tile(&mut ppu, 3, 0xFF, 0xFF);
+        write_vram(&mut ppu, 0x2000, &[1, 2, 3]);
+        write_vram(&mut ppu, 0x2040, &[1, 0, 2]);
+        // Palettes 0 to 3 for the top left, top right, bottom left and
+        // bottom right 16x16 quadrants.
+        write_vram(&mut ppu, 0x23C0, &[0b11_10_01_00]);
+        ppu.write(Ppu::MASK_ADDR, show_all().bits());
+
+        let frame = render(&mut ppu);
+        assert_eq!(pixel(&frame, 0, 0), 1);
+        assert_eq!(pixel(&frame, 7, 7), 1);
+        assert_eq!(pixel(&frame, 8, 0), 2);
+        assert_eq!(pixel(&frame, 16, 0), 4 + 3);
+        assert_eq!(pixel(&frame, 24, 0), 0);
+        assert_eq!(pixel(&frame, 0, 8), 0);
+        assert_eq!(pixel(&frame, 0, 16), 8 + 1);
+        assert_eq!(pixel(&frame, 16, 16), 12 + 2);
+    }
+
+    /// Sets one OAM entry: Y above the top line, tile, attributes and X.
+    fn set_sprite(ppu: &mut Ppu, sprite: usize, entry: [u8; 4]) {
+        ppu.oam_data[sprite * 4..sprite * 4 + 4].copy_from_slice(&entry);
+    }
+
+    #[test]
+    fn sprites_flip_and_go_behind_background() {
+        let mut ppu = test_ppu();
+        ppu.oam_data = [0xFF; Ppu::OAM_DATA_SIZE];
+        write_tile(&mut ppu, 1, 0xFF, 0x00);
+        // Only the left half of tile 4 is opaque.
+        write_tile(&mut ppu, 4, 0xF0, 0x00);
+        write_vram(&mut ppu, 0x20CF, &[1]);
+        set_sprite(&mut ppu, 0, [49, 4, 0x01, 40]);
+        set_sprite(&mut ppu, 1, [49, 4, 0x42, 80]);
+        set_sprite(&mut ppu, 2, [49, 4, 0x20, 120]);
+        ppu.write(Ppu::MASK_ADDR, show_all().bits());
+
+        let frame = render(&mut ppu);
+        assert_eq!(pixel(&frame, 40, 50), 0x10 + 4 + 1);
+        assert_eq!(pixel(&frame, 44, 50), 0);
+        assert_eq!(pixel(&frame, 43, 57), 0x10 + 4 + 1);
+        assert_eq!(pixel(&frame, 40, 58), 0);
+        assert_eq!(pixel(&frame, 80, 50), 0);
+        assert_eq!(pixel(&frame, 84, 50), 0x10 + 8 + 1);
+        // Sprite 2 is behind tile 1, which covers lines 48 to 55.
+        assert_eq!(pixel(&frame, 120, 55), 1);
+        assert_eq!(pixel(&frame, 120, 56), 0x10 + 1);
+    }
+
+    fn sprite_overflow(num_sprites: usize) -> bool {
+        let mut ppu = test_ppu();
+        ppu.oam_data = [0xFF; Ppu::OAM_DATA_SIZE];
+        for sprite in 0..num_sprites {
+            set_sprite(&mut ppu, sprite, [20, 0, 0, (sprite * 8) as u8]);
+        }
+        ppu.write(Ppu::MASK_ADDR, show_all().bits());
+        render(&mut ppu);
+        return ppu.status.contains(Status::SpriteOverflow);
+    }
+
+    /// Puts an opaque sprite 0 at `x` over an opaque background.
+    fn sprite_zero_hit(x: u8, mask: Mask) -> bool {
+        let mut ppu = test_ppu();
+        ppu.oam_data = [0xFF; Ppu::OAM_DATA_SIZE];
+        write_tile(&mut ppu, 1, 0xFF, 0x00);
+        write_vram(&mut ppu, 0x2000, &[1; 960]);
+        set_sprite(&mut ppu, 0, [20, 1, 0, x]);
+        ppu.write(Ppu::MASK_ADDR, mask.bits());
+        render(&mut ppu);
+        return ppu.status.contains(Status::SpriteZeroHit);
+    }
+
+    #[test]
+    fn nine_sprites_on_a_line_overflow() {
+        assert!(!sprite_overflow(8));
+        assert!(sprite_overflow(9));
+    }
+
+    #[test]
+    fn sprite_zero_hits_opaque_background() {
+        assert!(sprite_zero_hit(100, show_all()));
+        assert!(sprite_zero_hit(0, show_all()));
+    }
+
+    #[test]
+    fn no_sprite_zero_hit_at_last_dot() {
+        assert!(sprite_zero_hit(254, show_all()));
+        assert!(!sprite_zero_hit(255, show_all()));
+    }
+
+    #[test]
+    fn no_sprite_zero_hit_in_clipped_left_column() {
+        assert!(!sprite_zero_hit(0, show_all() & !Mask::LeftmostSprite));
+        assert!(!sprite_zero_hit(0, show_all() & !Mask::LeftmostBackground));
+    }
+
+    /// Runs up to `dot` of `scanline`, stopping before that dot is drawn.
+    fn run_to_dot(ppu: &mut Ppu, scanline: usize, dot: i64) {
+        while ppu.curr_scanline != scanline || ppu.clock != dot {
+            ppu.tick(1);
+        }
+    }
+
+    #[test]
+    fn horizontal_scroll_copied_at_dot_257() {
+        let mut ppu = test_ppu();
+        ppu.write(Ppu::MASK_ADDR, show_all().bits());
+        run_to_dot(&mut ppu, 10, 257);
+        ppu.address.t = 0x7FFF;
+        ppu.address.v = 0;
+        ppu.tick(1);
+        assert_eq!(ppu.address.v, 0x041F);
+        ppu.address.v = 0;
+        ppu.tick(1);
+        assert_eq!(ppu.address.v, 0);
+    }
+
+    #[test]
+    fn vertical_scroll_copied_at_pre_render_dots_280_to_304() {
+        let mut ppu = test_ppu();
+        ppu.write(Ppu::MASK_ADDR, show_all().bits());
+        let pre_render = ppu.timing.pre_render_scanline();
+        run_to_dot(&mut ppu, pre_render, 279);
+        ppu.address.t = 0x7FFF;
+        ppu.address.v = 0;
+        ppu.tick(1);
+        assert_eq!(ppu.address.v, 0);
+        ppu.tick(1);
+        assert_eq!(ppu.address.v, 0x7BE0);
+
+        run_to_dot(&mut ppu, pre_render, 304);
+        ppu.address.v = 0;
+        ppu.tick(1);
+        assert_eq!(ppu.address.v, 0x7BE0);
+        ppu.address.v = 0;
+        ppu.tick(1);
+        assert_eq!(ppu.address.v, 0);
+    }
+
+    #[test]
+    fn sprite_backdrops_mirror_background_entries() {
+        let mut ppu = test_ppu();
+        write_vram(&mut ppu, 0x3F10, &[0x2A]);
+        set_address(&mut ppu, 0x3F00);
+        assert_eq!(ppu.read(Ppu::DATA_ADDR), 0x2A);
+        write_vram(&mut ppu, 0x3F0C, &[0x15]);
+        set_address(&mut ppu, 0x3F1C);
+        assert_eq!(ppu.read(Ppu::DATA_ADDR), 0x15);
+    }
+
+    #[test]
+    fn palette_read_fills_buffer_from_nametable() {
+        let mut ppu = test_ppu();
+        write_vram(&mut ppu, 0x2F00, &[0x55]);
+        write_vram(&mut ppu, 0x3F00, &[0x0F]);
+        set_address(&mut ppu, 0x3F00);
+        assert_eq!(ppu.read(Ppu::DATA_ADDR), 0x0F);
+        set_address(&mut ppu, 0x2000);
+        assert_eq!(ppu.read(Ppu::DATA_ADDR), 0x55);
+    }
+
+    #[test]
+    fn greyscale_keeps_only_the_brightness_bits() {
+        let mut ppu = test_ppu();
+        write_vram(&mut ppu, 0x3F00, &[0x2A]);
+        ppu.write(Ppu::MASK_ADDR, show_all().bits());
+        assert_eq!(pixel(&render(&mut ppu), 0, 0), 0x2A);
+        ppu.write(Ppu::MASK_ADDR, (show_all() | Mask::Greyscale).bits());
+        assert_eq!(pixel(&render(&mut ppu), 0, 0), 0x20);
+    }
+
+    /// Counts the dots from the start of this frame to the start of the next.
+    fn frame_dots(ppu: &mut Ppu) -> usize {
+        let mut result = 0;
+        loop {
+            ppu.tick(1);
+            result += 1;
+            if ppu.curr_scanline == 0 && ppu.clock == 0 {
+                return result;
+            }
+        }
+    }
+
+    #[test]
+    fn odd_frames_skip_a_dot_while_rendering() {
+        let mut ppu = test_ppu();
+        ppu.write(Ppu::MASK_ADDR, show_all().bits());
+        assert_eq!(frame_dots(&mut ppu), 89342);
+        assert_eq!(frame_dots(&mut ppu), 89341);
+        assert_eq!(frame_dots(&mut ppu), 89342);
+        ppu.write(Ppu::MASK_ADDR, 0);
+        assert_eq!(frame_dots(&mut ppu), 89342);
+    }
 }
@@ -1,37 +1,116 @@
+const COARSE_X: u16 = 0x001F;
+const COARSE_Y: u16 = 0x03E0;
+const NAMETABLE_X: u16 = 0x0400;
+const NAMETABLE_Y: u16 = 0x0800;
+const NAMETABLE: u16 = NAMETABLE_X | NAMETABLE_Y;
+const FINE_Y: u16 = 0x7000;
+const HORIZONTAL: u16 = NAMETABLE_X | COARSE_X;
+const VERTICAL: u16 = FINE_Y | NAMETABLE_Y | COARSE_Y;
+const ADDRESS_MASK: u16 = 0x3FFF;
+const REGISTER_MASK: u16 = 0x7FFF;
+const LAST_COARSE_X: u16 = 31;
+const LAST_ROW: u16 = 29;
+const LAST_COARSE_Y: u16 = 31;
+
+/// The PPU's internal address registers, shared by $2005, $2006 and
+/// rendering. v is the current VRAM address, laid out as
+/// yyy NN YYYYY XXXXX (fine Y, nametable, coarse Y, coarse X). t holds the
+/// address being written until it is copied into v, fine_x is the scroll
+/// within a tile, and latch is the write toggle $2005 and $2006 share.
 pub struct AddressRegister {
-    pub bytes: [u8; 2],
+    pub v: u16,
+    pub t: u16,
+    pub fine_x: u8,
     pub latch: usize,
 }
 
 impl Default for AddressRegister {
     fn default() -> Self {
         Self {
-            bytes: [0, 0],
+            v: 0,
+            t: 0,
+            fine_x: 0,
             latch: 0,
         }
     }
 }
 
 impl AddressRegister {
-    pub fn set(&mut self, value: u16) {
-        self.bytes = (value as u16).to_be_bytes();
-    }
-
     pub fn read(&self) -> u16 {
-        return u16::from_be_bytes(self.bytes);
+        return self.v & ADDRESS_MASK;
     }
 
+    /// $2006: high byte then low byte. The second write copies t into v.
     pub fn write(&mut self, value: u8) {
-        self.bytes[self.latch] = value;
-        self.latch = (self.latch + 1) % 2;
+        if self.latch == 0 {
+            self.t = (self.t & 0x00FF) | ((value as u16 & 0x3F) << 8);
+        } else {
+            self.t = (self.t & 0xFF00) | value as u16;
+            self.v = self.t;
+        }
+        self.latch ^= 1;
+    }
+
+    /// $2005: X scroll then Y scroll.
+    pub fn scroll_write(&mut self, value: u8) {
+        let value = value as u16;
+        if self.latch == 0 {
+            self.t = (self.t & !COARSE_X) | (value >> 3);
+            self.fine_x = (value & 0x07) as u8;
+        } else {
+            self.t = (self.t & !(FINE_Y | COARSE_Y)) | ((value & 0x07) << 12) | ((value >> 3) << 5);
+        }
+        self.latch ^= 1;
+    }
+
+    /// $2000: the base nametable bits go into t.
+    pub fn set_nametable(&mut self, control: u8) {
+        self.t = (self.t & !NAMETABLE) | ((control as u16 & 0x03) << 10);
     }
 
     pub fn increment(&mut self, value: u16) {
-        let result = self.read().wrapping_add(value);
-        self.set(result);
+        self.v = self.v.wrapping_add(value) & REGISTER_MASK;
     }
 
     pub fn reset_latch(&mut self) {
         self.latch = 0;
     }
+
+    pub fn increment_coarse_x(&mut self) {
+        if self.v & COARSE_X == LAST_COARSE_X {
+            self.v &= !COARSE_X;
+            self.v ^= NAMETABLE_X;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    /// Moves down a pixel row, wrapping into the next nametable after row 29.
+    /// Coarse Y values of 30 and 31 point into attribute memory and wrap
+    /// without switching nametables.
+    pub fn increment_y(&mut self) {
+        if self.v & FINE_Y != FINE_Y {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !FINE_Y;
+        let mut coarse_y = (self.v & COARSE_Y) >> 5;
+        if coarse_y == LAST_ROW {
+            coarse_y = 0;
+            self.v ^= NAMETABLE_Y;
+        } else if coarse_y == LAST_COARSE_Y {
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !COARSE_Y) | (coarse_y << 5);
+    }
+
+    pub fn copy_horizontal(&mut self) {
+        self.v = (self.v & !HORIZONTAL) | (self.t & HORIZONTAL);
+    }
+
+    pub fn copy_vertical(&mut self) {
+        self.v = (self.v & !VERTICAL) | (self.t & VERTICAL);
+    }
 }
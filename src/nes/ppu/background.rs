@@ -0,0 +1,69 @@
+/// The background half of the rendering pipeline. Each tile's fetches land
+/// in the latches over 8 dots and are then loaded into the low byte of the
+/// shift registers, which shift left once a dot so bit 15 is always the
+/// pixel being drawn.
+pub struct Background {
+    pub nametable_latch: u8,
+    pub attribute_latch: u8,
+    pub pattern_low_latch: u8,
+    pub pattern_high_latch: u8,
+    pub pattern_low: u16,
+    pub pattern_high: u16,
+    pub attribute_low: u16,
+    pub attribute_high: u16,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self {
+            nametable_latch: 0,
+            attribute_latch: 0,
+            pattern_low_latch: 0,
+            pattern_high_latch: 0,
+            pattern_low: 0,
+            pattern_high: 0,
+            attribute_low: 0,
+            attribute_high: 0,
+        }
+    }
+}
+
+impl Background {
+    /// Moves the latched tile into the shift registers. The attribute bits
+    /// are the same for all 8 pixels, so they are spread across the byte.
+    pub fn load(&mut self) {
+        self.pattern_low = (self.pattern_low & 0xFF00) | self.pattern_low_latch as u16;
+        self.pattern_high = (self.pattern_high & 0xFF00) | self.pattern_high_latch as u16;
+        let attribute_low = if self.attribute_latch & 0b01 != 0 {
+            0xFF
+        } else {
+            0
+        };
+        let attribute_high = if self.attribute_latch & 0b10 != 0 {
+            0xFF
+        } else {
+            0
+        };
+        self.attribute_low = (self.attribute_low & 0xFF00) | attribute_low;
+        self.attribute_high = (self.attribute_high & 0xFF00) | attribute_high;
+    }
+
+    pub fn shift(&mut self) {
+        self.pattern_low <<= 1;
+        self.pattern_high <<= 1;
+        self.attribute_low <<= 1;
+        self.attribute_high <<= 1;
+    }
+
+    /// The palette RAM index of the current pixel, offset by fine X. Pixels
+    /// with a pattern value of 0 all share the backdrop at index 0.
+    pub fn pixel(&self, fine_x: u8) -> u8 {
+        let bit = 15 - fine_x as u16;
+        let pattern = ((self.pattern_high >> bit) & 1) << 1 | ((self.pattern_low >> bit) & 1);
+        if pattern == 0 {
+            return 0;
+        }
+        let attribute = ((self.attribute_high >> bit) & 1) << 1 | ((self.attribute_low >> bit) & 1);
+        return (attribute << 2 | pattern) as u8;
+    }
+}
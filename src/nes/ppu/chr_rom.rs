@@ -1,15 +1,46 @@
-use crate::memory::Memory;
+use crate::nes::{
+    cart::mapper::{self, MapperRef},
+    memory::Memory,
+};
+
+use super::VramMirror;
 
 pub struct ChrRom {
-    pub memory: Vec<u8>,
+    pub mapper: MapperRef,
+}
+
+impl Default for ChrRom {
+    fn default() -> Self {
+        Self {
+            mapper: mapper::empty(),
+        }
+    }
 }
 
 impl Memory for ChrRom {
     fn read(&mut self, address: usize) -> u8 {
-        todo!()
+        return self.mapper.borrow_mut().ppu_read(address);
     }
 
     fn write(&mut self, address: usize, value: u8) {
-        todo!()
+        self.mapper.borrow_mut().ppu_write(address, value);
+    }
+}
+
+impl ChrRom {
+    pub fn new(mapper: MapperRef) -> Self {
+        return Self { mapper: mapper };
     }
-}
\ No newline at end of file
+
+    pub fn mirroring(&self) -> Option<VramMirror> {
+        return self.mapper.borrow().mirroring();
+    }
+
+    pub fn nametable_read(&mut self, address: usize) -> Option<u8> {
+        return self.mapper.borrow_mut().nametable_read(address);
+    }
+
+    pub fn nametable_write(&mut self, address: usize, value: u8) -> bool {
+        return self.mapper.borrow_mut().nametable_write(address, value);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::nes::cart::header::{ConsoleType, Header};
+
+use super::SYSTEM_PALLETE;
+
+const COLOR_MASK: usize = 0x3F;
+const COLOR_BITS: usize = 6;
+/// On the 2C02 each emphasis bit darkens the other two channels.
+const EMPHASIS_ATTENUATION: f32 = 0.816;
+
+/// The RGB PPUs output each channel at 3 bits, written here in octal so the
+/// digits read as red, green and blue.
+const RGB_PALETTE: [u16; 64] = [
+    0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022,
+    0o000, 0o000, 0o000, 0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140,
+    0o040, 0o053, 0o044, 0o000, 0o000, 0o000, 0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740,
+    0o750, 0o660, 0o360, 0o070, 0o276, 0o077, 0o000, 0o000, 0o000, 0o777, 0o567, 0o657, 0o757,
+    0o747, 0o755, 0o764, 0o772, 0o773, 0o572, 0o473, 0o276, 0o467, 0o000, 0o000, 0o000,
+];
+
+const RP2C04_0001_PALETTE: [u16; 64] = [
+    0o755, 0o637, 0o700, 0o447, 0o044, 0o120, 0o222, 0o704, 0o777, 0o333, 0o750, 0o503, 0o403,
+    0o660, 0o320, 0o777, 0o357, 0o653, 0o310, 0o360, 0o467, 0o657, 0o764, 0o027, 0o760, 0o276,
+    0o000, 0o200, 0o666, 0o444, 0o707, 0o014, 0o003, 0o567, 0o757, 0o070, 0o077, 0o022, 0o053,
+    0o507, 0o000, 0o420, 0o747, 0o510, 0o407, 0o006, 0o740, 0o000, 0o000, 0o140, 0o555, 0o031,
+    0o572, 0o326, 0o770, 0o630, 0o020, 0o036, 0o040, 0o111, 0o773, 0o737, 0o430, 0o473,
+];
+
+const RP2C04_0002_PALETTE: [u16; 64] = [
+    0o000, 0o750, 0o430, 0o572, 0o473, 0o737, 0o044, 0o567, 0o700, 0o407, 0o773, 0o747, 0o777,
+    0o637, 0o467, 0o040, 0o020, 0o357, 0o510, 0o666, 0o053, 0o360, 0o200, 0o447, 0o222, 0o707,
+    0o003, 0o276, 0o657, 0o320, 0o000, 0o326, 0o403, 0o764, 0o740, 0o757, 0o036, 0o310, 0o555,
+    0o006, 0o507, 0o760, 0o333, 0o120, 0o027, 0o000, 0o660, 0o777, 0o653, 0o111, 0o070, 0o630,
+    0o022, 0o014, 0o704, 0o140, 0o000, 0o077, 0o420, 0o770, 0o755, 0o503, 0o031, 0o444,
+];
+
+const RP2C04_0003_PALETTE: [u16; 64] = [
+    0o507, 0o737, 0o473, 0o555, 0o040, 0o777, 0o567, 0o120, 0o014, 0o000, 0o764, 0o320, 0o704,
+    0o666, 0o653, 0o467, 0o447, 0o044, 0o503, 0o027, 0o140, 0o430, 0o630, 0o053, 0o333, 0o326,
+    0o000, 0o006, 0o700, 0o510, 0o747, 0o755, 0o637, 0o020, 0o003, 0o770, 0o111, 0o750, 0o740,
+    0o777, 0o360, 0o403, 0o357, 0o707, 0o036, 0o444, 0o000, 0o310, 0o077, 0o200, 0o572, 0o757,
+    0o420, 0o070, 0o660, 0o222, 0o031, 0o000, 0o657, 0o773, 0o407, 0o276, 0o760, 0o022,
+];
+
+const RP2C04_0004_PALETTE: [u16; 64] = [
+    0o430, 0o326, 0o044, 0o660, 0o000, 0o755, 0o014, 0o630, 0o555, 0o310, 0o070, 0o003, 0o764,
+    0o770, 0o040, 0o572, 0o737, 0o200, 0o027, 0o747, 0o000, 0o222, 0o510, 0o740, 0o653, 0o053,
+    0o447, 0o140, 0o403, 0o000, 0o473, 0o357, 0o503, 0o031, 0o420, 0o006, 0o407, 0o507, 0o333,
+    0o704, 0o022, 0o666, 0o036, 0o020, 0o111, 0o773, 0o444, 0o707, 0o757, 0o777, 0o320, 0o700,
+    0o760, 0o276, 0o777, 0o467, 0o000, 0o750, 0o637, 0o567, 0o360, 0o657, 0o077, 0o120,
+];
+
+/// The 2C04 variants scramble the same 64 colors in different orders.
+const RP2C04_PALETTES: [&[u16; 64]; 4] = [
+    &RP2C04_0001_PALETTE,
+    &RP2C04_0002_PALETTE,
+    &RP2C04_0003_PALETTE,
+    &RP2C04_0004_PALETTE,
+];
+
+/// The low bits of PPUSTATUS on each 2C05, which Vs. games check to make sure
+/// they are running on the board they shipped with.
+const RC2C05_STATUS_IDS: [u8; 5] = [0x1B, 0x3D, 0x1C, 0x1B, 0x00];
+
+/// Which PPU chip the console has. Vs. System and PlayChoice-10 boards use
+/// RGB PPUs with their own palettes instead of the composite 2C02.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PpuModel {
+    Rp2C02,
+    Rp2C03,
+    /// RP2C04-0001 to -0004, numbered from 0.
+    Rp2C04(usize),
+    /// RC2C05-01 to -05, numbered from 0.
+    Rc2C05(usize),
+}
+
+impl Default for PpuModel {
+    fn default() -> Self {
+        Self::Rp2C02
+    }
+}
+
+impl PpuModel {
+    pub fn new(header: &Header) -> Self {
+        let result = match header.console_type() {
+            ConsoleType::Nes | ConsoleType::Extended => Self::Rp2C02,
+            ConsoleType::PlayChoice10 => Self::Rp2C03,
+            ConsoleType::VsSystem => match header.vs_ppu_type() {
+                ppu_type @ 2..=5 => Self::Rp2C04(ppu_type as usize - 2),
+                ppu_type @ 8..=12 => Self::Rc2C05(ppu_type as usize - 8),
+                _ => Self::Rp2C03,
+            },
+        };
+        return result;
+    }
+
+    pub fn name(&self) -> String {
+        let result = match self {
+            Self::Rp2C02 => "RP2C02".to_string(),
+            Self::Rp2C03 => "RP2C03".to_string(),
+            Self::Rp2C04(variant) => format!("RP2C04-{:04}", variant + 1),
+            Self::Rc2C05(variant) => format!("RC2C05-{:02}", variant + 1),
+        };
+        return result;
+    }
+
+    /// The color of a PPU output value: a 6-bit color index with the red,
+    /// green and blue emphasis bits from PPUMASK above it.
+    pub fn rgb(&self, value: usize) -> (u8, u8, u8) {
+        let index = value & COLOR_MASK;
+        let emphasis = value >> COLOR_BITS;
+        let is_emphasized = |channel: usize| emphasis & (1 << channel) != 0;
+        let color = match self {
+            Self::Rp2C02 => return emphasize(SYSTEM_PALLETE[index], emphasis),
+            Self::Rp2C03 | Self::Rc2C05(_) => RGB_PALETTE[index],
+            Self::Rp2C04(variant) => RP2C04_PALETTES[*variant][index],
+        };
+        // The RGB PPUs drive an emphasized channel at full strength instead.
+        let level = |shift: u16, channel: usize| {
+            if is_emphasized(channel) {
+                return u8::MAX;
+            }
+            return (((color >> shift) & 0o7) * 255 / 7) as u8;
+        };
+        return (level(6, 0), level(3, 1), level(0, 2));
+    }
+
+    pub fn status_id(&self) -> u8 {
+        return match self {
+            Self::Rc2C05(variant) => RC2C05_STATUS_IDS[*variant],
+            _ => 0,
+        };
+    }
+
+    /// The 2C05 has PPUCTRL and PPUMASK at each other's addresses.
+    pub fn is_control_swapped(&self) -> bool {
+        return matches!(self, Self::Rc2C05(_));
+    }
+}
+
+/// Applies the 2C02's emphasis bits to a color, darkening every channel that
+/// isn't emphasized.
+pub fn emphasize(color: (u8, u8, u8), emphasis: usize) -> (u8, u8, u8) {
+    if emphasis == 0 {
+        return color;
+    }
+    let attenuate = |level: u8, channel: usize| {
+        if emphasis & (1 << channel) != 0 {
+            return level;
+        }
+        return (level as f32 * EMPHASIS_ATTENUATION) as u8;
+    };
+    return (
+        attenuate(color.0, 0),
+        attenuate(color.1, 1),
+        attenuate(color.2, 2),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rp2c04_palettes_share_colors() {
+        let sorted = |palette: &[u16; 64]| {
+            let mut result = palette.to_vec();
+            result.sort();
+            return result;
+        };
+        let colors = sorted(RP2C04_PALETTES[0]);
+        for palette in RP2C04_PALETTES {
+            assert_eq!(sorted(palette), colors);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use super::{model, Frame, Picture, PixelFormat, PpuModel};
+
+const NUM_COLORS: usize = 64;
+const NUM_VALUES: usize = 512;
+const COLOR_MASK: usize = 0x3F;
+const COLOR_BITS: usize = 6;
+
+/// Maps each 9-bit PPU output value to a color. This is the conversion stage
+/// between the PPU's `Picture` and the `Frame` a frontend draws.
+pub struct Palette {
+    colors: Vec<(u8, u8, u8)>,
+}
+
+impl Palette {
+    /// The colors the given PPU chip produces.
+    pub fn new(model: PpuModel) -> Self {
+        let colors = (0..NUM_VALUES).map(|value| model.rgb(value)).collect();
+        return Self { colors: colors };
+    }
+
+    /// Reads a `.pal` file: 64 RGB triples, or 512 when it also covers every
+    /// emphasis combination. A 64 color palette gets the 2C02's emphasis.
+    pub fn from_pal(data: &[u8]) -> Option<Self> {
+        let triples: Vec<(u8, u8, u8)> = data
+            .chunks_exact(3)
+            .map(|rgb| (rgb[0], rgb[1], rgb[2]))
+            .collect();
+        let colors = match data.len() {
+            len if len == NUM_COLORS * 3 => (0..NUM_VALUES)
+                .map(|value| model::emphasize(triples[value & COLOR_MASK], value >> COLOR_BITS))
+                .collect(),
+            len if len == NUM_VALUES * 3 => triples,
+            _ => return None,
+        };
+        return Some(Self { colors: colors });
+    }
+
+    pub fn rgb(&self, value: u16) -> (u8, u8, u8) {
+        return self.colors[value as usize % NUM_VALUES];
+    }
+
+    pub fn convert(&self, picture: &Picture, format: PixelFormat) -> Frame {
+        let mut frame = Frame::with_format(format);
+        for y in 0..Picture::HEIGHT {
+            for x in 0..Picture::WIDTH {
+                frame.write(x, y, self.rgb(picture.get(x, y)));
+            }
+        }
+        return frame;
+    }
+}
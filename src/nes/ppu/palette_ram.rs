@@ -0,0 +1,43 @@
+use crate::nes::memory::Memory;
+
+/// The 32 bytes of palette RAM at $3F00, mirrored up to $3FFF. Entry 0 of
+/// each sprite palette is the same byte as the matching background entry,
+/// and only the low 6 bits of each entry exist.
+pub struct PaletteRam {
+    pub memory: [u8; Self::SIZE],
+}
+
+impl Default for PaletteRam {
+    fn default() -> Self {
+        Self {
+            memory: [0; Self::SIZE],
+        }
+    }
+}
+
+impl Memory for PaletteRam {
+    fn read(&mut self, address: usize) -> u8 {
+        return self.memory[Self::map_address(address)];
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        self.memory[Self::map_address(address)] = value & Self::COLOR_MASK;
+    }
+}
+
+impl PaletteRam {
+    pub const BEGIN: usize = 0x3F00;
+    pub const END: usize = 0x3FFF;
+    const SIZE: usize = 0x20;
+    const COLOR_MASK: u8 = 0x3F;
+    const SPRITE_BACKDROP_MASK: usize = 0x13;
+    const SPRITE_PALETTES: usize = 0x10;
+
+    fn map_address(address: usize) -> usize {
+        let index = address % Self::SIZE;
+        if index & Self::SPRITE_BACKDROP_MASK == Self::SPRITE_PALETTES {
+            return index - Self::SPRITE_PALETTES;
+        }
+        return index;
+    }
+}
@@ -0,0 +1,30 @@
+/// A frame as the PPU outputs it: one 9-bit value per pixel, the 6-bit color
+/// index from palette RAM with the three PPUMASK emphasis bits above it.
+/// Turning these into colors is left to a `Palette`.
+pub struct Picture {
+    pub data: Vec<u16>,
+}
+
+impl Picture {
+    pub const WIDTH: usize = 256;
+    pub const HEIGHT: usize = 240;
+
+    pub fn new() -> Self {
+        return Self {
+            data: vec![0; Self::WIDTH * Self::HEIGHT],
+        };
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u16 {
+        return self.data[x + y * Self::WIDTH];
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: u16) {
+        self.data[x + y * Self::WIDTH] = value;
+    }
+
+    pub fn write_line(&mut self, y: usize, line: &[u16; Self::WIDTH]) {
+        let begin = y * Self::WIDTH;
+        self.data[begin..begin + Self::WIDTH].copy_from_slice(line);
+    }
+}
@@ -0,0 +1,175 @@
+const MAX_SPRITES: usize = 8;
+const NUM_OAM_SPRITES: usize = 64;
+const SPRITE_BYTES: usize = 4;
+const SECONDARY_OAM_SIZE: usize = MAX_SPRITES * SPRITE_BYTES;
+const SPRITE_WIDTH: usize = 8;
+const SPRITE_PALETTES: u8 = 0x10;
+const TILE_SIZE: usize = 16;
+const LARGE_SPRITE_BANK_SIZE: usize = 0x1000;
+
+const PALETTE_MASK: u8 = 0b00000011;
+const BEHIND_BACKGROUND: u8 = 0b00100000;
+const FLIP_HORIZONTAL: u8 = 0b01000000;
+const FLIP_VERTICAL: u8 = 0b10000000;
+
+/// A sprite loaded for the line being drawn, with its pattern row already
+/// fetched and flipped.
+#[derive(Clone, Copy)]
+pub struct SpriteSlot {
+    pub x: u8,
+    pub attributes: u8,
+    pub pattern_low: u8,
+    pub pattern_high: u8,
+}
+
+impl Default for SpriteSlot {
+    fn default() -> Self {
+        Self {
+            x: 0xFF,
+            attributes: 0,
+            pattern_low: 0,
+            pattern_high: 0,
+        }
+    }
+}
+
+/// The sprite half of the rendering pipeline. Each line picks the first 8
+/// sprites in OAM that cover the next line into secondary OAM, whose patterns
+/// are fetched at dots 257-320 into the slots drawn on that next line.
+pub struct Sprites {
+    pub secondary_oam: [u8; SECONDARY_OAM_SIZE],
+    pub num_found: usize,
+    pub is_zero_found: bool,
+    pub slots: [SpriteSlot; MAX_SPRITES],
+    pub num_slots: usize,
+    pub is_zero_loaded: bool,
+}
+
+impl Default for Sprites {
+    fn default() -> Self {
+        Self {
+            secondary_oam: [0xFF; SECONDARY_OAM_SIZE],
+            num_found: 0,
+            is_zero_found: false,
+            slots: [Default::default(); MAX_SPRITES],
+            num_slots: 0,
+            is_zero_loaded: false,
+        }
+    }
+}
+
+impl Sprites {
+    pub fn clear_secondary(&mut self) {
+        self.secondary_oam = [0xFF; SECONDARY_OAM_SIZE];
+        self.num_found = 0;
+        self.is_zero_found = false;
+    }
+
+    /// Fills secondary OAM and returns whether the overflow flag should be
+    /// set. Once 8 sprites are found the hardware keeps looking for a ninth
+    /// but wrongly steps the byte offset along with the sprite number, so it
+    /// checks tile, attribute and X bytes as if they were Y coordinates.
+    pub fn evaluate(&mut self, oam: &[u8], scanline: usize, height: usize) -> bool {
+        let is_in_range = |y: u8| scanline.wrapping_sub(y as usize) < height;
+        let mut sprite = 0;
+        while sprite < NUM_OAM_SPRITES && self.num_found < MAX_SPRITES {
+            let index = sprite * SPRITE_BYTES;
+            if is_in_range(oam[index]) {
+                let slot = self.num_found * SPRITE_BYTES;
+                self.secondary_oam[slot..slot + SPRITE_BYTES]
+                    .copy_from_slice(&oam[index..index + SPRITE_BYTES]);
+                self.is_zero_found |= sprite == 0;
+                self.num_found += 1;
+            }
+            sprite += 1;
+        }
+        let mut byte = 0;
+        while sprite < NUM_OAM_SPRITES {
+            if is_in_range(oam[sprite * SPRITE_BYTES + byte]) {
+                return true;
+            }
+            sprite += 1;
+            byte = (byte + 1) % SPRITE_BYTES;
+        }
+        return false;
+    }
+
+    /// The pattern address of the row a secondary OAM entry needs on the
+    /// next line. 8x16 sprites pick their bank with bit 0 of the tile number
+    /// instead of using `pattern_base`.
+    pub fn pattern_address(
+        &self,
+        slot: usize,
+        scanline: usize,
+        height: usize,
+        pattern_base: usize,
+    ) -> usize {
+        let sprite = &self.secondary_oam[slot * SPRITE_BYTES..(slot + 1) * SPRITE_BYTES];
+        let mut row = scanline.wrapping_sub(sprite[0] as usize) % height;
+        if sprite[2] & FLIP_VERTICAL != 0 {
+            row = height - 1 - row;
+        }
+        let tile = sprite[1] as usize;
+        if height == SPRITE_WIDTH {
+            return pattern_base + tile * TILE_SIZE + row;
+        }
+        let bank = (tile & 0x01) * LARGE_SPRITE_BANK_SIZE;
+        let tile = (tile & 0xFE) + row / SPRITE_WIDTH;
+        return bank + tile * TILE_SIZE + row % SPRITE_WIDTH;
+    }
+
+    pub fn load(&mut self, slot: usize, pattern_low: u8, pattern_high: u8) {
+        let sprite = &self.secondary_oam[slot * SPRITE_BYTES..(slot + 1) * SPRITE_BYTES];
+        let attributes = sprite[2];
+        let (pattern_low, pattern_high) = if attributes & FLIP_HORIZONTAL != 0 {
+            (pattern_low.reverse_bits(), pattern_high.reverse_bits())
+        } else {
+            (pattern_low, pattern_high)
+        };
+        self.slots[slot] = SpriteSlot {
+            x: sprite[3],
+            attributes: attributes,
+            pattern_low: pattern_low,
+            pattern_high: pattern_high,
+        };
+        if slot + 1 == MAX_SPRITES {
+            self.num_slots = self.num_found;
+            self.is_zero_loaded = self.is_zero_found;
+        }
+    }
+
+    /// The palette RAM index of the frontmost opaque sprite pixel at x, and
+    /// whether that sprite sits behind the background.
+    pub fn pixel(&self, x: usize) -> Option<(u8, bool)> {
+        for sprite in &self.slots[..self.num_slots] {
+            let column = x.wrapping_sub(sprite.x as usize);
+            if column >= SPRITE_WIDTH {
+                continue;
+            }
+            let bit = 7 - column;
+            let pattern =
+                ((sprite.pattern_high >> bit) & 1) << 1 | ((sprite.pattern_low >> bit) & 1);
+            if pattern == 0 {
+                continue;
+            }
+            let palette = (sprite.attributes & PALETTE_MASK) << 2;
+            let is_behind = sprite.attributes & BEHIND_BACKGROUND != 0;
+            return Some((SPRITE_PALETTES | palette | pattern, is_behind));
+        }
+        return None;
+    }
+
+    /// Whether sprite 0 is on this line and has an opaque pixel at x.
+    pub fn is_zero_opaque(&self, x: usize) -> bool {
+        if !self.is_zero_loaded {
+            return false;
+        }
+        let sprite = &self.slots[0];
+        let column = x.wrapping_sub(sprite.x as usize);
+        if column >= SPRITE_WIDTH {
+            return false;
+        }
+        let bit = 7 - column;
+        return (sprite.pattern_low | sprite.pattern_high) >> bit & 1 != 0;
+    }
+}
@@ -4,7 +4,7 @@ use bitmask_enum::bitmask;
 #[derive(Default)]
 pub enum Status {
     OpenBus = 0b00011111,
-    SpriteOverflow,
-    SpriteZeroHit,
-    VBlank,
+    SpriteOverflow = 0b00100000,
+    SpriteZeroHit = 0b01000000,
+    VBlank = 0b10000000,
 }
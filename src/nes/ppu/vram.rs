@@ -1,14 +1,17 @@
 use crate::nes::memory::Memory;
 
+/// Nametable memory. The first 2 KiB is the console's own VRAM, the second is
+/// the extra RAM a four-screen cartridge supplies and is only reachable in
+/// that mode.
 pub struct VRam {
-    pub memory: [u8; Self::SIZE],
+    pub memory: [u8; Self::FOUR_SCREEN_SIZE],
     pub mirror: VramMirror,
 }
 
 impl Default for VRam {
     fn default() -> Self {
         Self {
-            memory: [0; Self::SIZE],
+            memory: [0; Self::FOUR_SCREEN_SIZE],
             mirror: VramMirror::Horizontal,
         }
     }
@@ -30,36 +33,45 @@ impl VRam {
     const BEGIN: usize = 0x2000;
     const END: usize = 0x3FFF;
     const SIZE: usize = 0x800;
+    const FOUR_SCREEN_SIZE: usize = 2 * Self::SIZE;
     const TABLE_SIZE: usize = 0x400;
 
+    pub fn new(mirror: VramMirror) -> Self {
+        return Self {
+            memory: [0; Self::FOUR_SCREEN_SIZE],
+            mirror: mirror,
+        };
+    }
+
     fn map_address(&self, address: usize) -> usize {
         let mask = 0x2FFF;
-        let address = (address & mask) - Self::BEGIN;
+        let index = (address & mask) - Self::BEGIN;
         let result = match self.mirror {
-            VramMirror::Vertical => self.map_vertical(address),
-            VramMirror::Horizontal => self.map_horizontal(address),
+            VramMirror::Vertical => self.map_vertical(index),
+            VramMirror::Horizontal => self.map_horizontal(index),
+            VramMirror::SingleScreenA => index % Self::TABLE_SIZE,
+            VramMirror::SingleScreenB => Self::TABLE_SIZE + index % Self::TABLE_SIZE,
+            VramMirror::FourScreen => index,
         };
         return result;
     }
 
-    fn map_horizontal(&self, address: usize) -> usize {
-        let index = address - Self::BEGIN;
+    fn map_horizontal(&self, index: usize) -> usize {
         let table_number = index / Self::TABLE_SIZE;
         const TABLE_A: usize = 0;
         const TABLE_A_MIRROR: usize = 1;
         const TABLE_B: usize = 2;
         const TABLE_B_MIRROR: usize = 3;
         let result = match table_number {
-            TABLE_A => 0,
+            TABLE_A => index,
             TABLE_A_MIRROR | TABLE_B => index - Self::TABLE_SIZE,
             TABLE_B_MIRROR => index - (2 * Self::TABLE_SIZE),
-            _ => panic!("invalid ppu address at 0x{:04X}", address),
+            _ => panic!("invalid ppu address at 0x{:04X}", index + Self::BEGIN),
         };
         return result;
     }
 
-    fn map_vertical(&self, address: usize) -> usize {
-        let index = address - Self::BEGIN;
+    fn map_vertical(&self, index: usize) -> usize {
         let table_number = index / Self::TABLE_SIZE;
         const TABLE_A: usize = 0;
         const TABLE_A_MIRROR: usize = 2;
@@ -68,13 +80,17 @@ impl VRam {
         let result = match table_number {
             TABLE_A | TABLE_B => index,
             TABLE_A_MIRROR | TABLE_B_MIRROR => index - (2 * Self::TABLE_SIZE),
-            _ => panic!("invalid ppu address at 0x{:04X}", address),
+            _ => panic!("invalid ppu address at 0x{:04X}", index + Self::BEGIN),
         };
         return result;
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum VramMirror {
     Horizontal,
     Vertical,
+    SingleScreenA,
+    SingleScreenB,
+    FourScreen,
 }
@@ -0,0 +1,77 @@
+use super::cart::header::Region;
+
+const DOTS_PER_SCANLINE: f64 = 341.0;
+
+/// Clock rates and frame layout for the console region being emulated.
+/// Chosen once when the console is built and shared by everything that
+/// converts between CPU cycles, PPU dots and real time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Timing {
+    pub region: Region,
+    pub cpu_frequency: f64,
+    pub num_scanlines: usize,
+    pub vblank_scanline: usize,
+    /// PPU dots per CPU cycle as a fraction, 3/1 except for PAL's 16/5.
+    pub ppu_dots_per_cycle: i64,
+    pub ppu_cycle_divisor: i64,
+    pub has_odd_frame_skip: bool,
+    /// The 2C07 wires its red and green emphasis bits the other way round.
+    pub is_emphasis_swapped: bool,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self::new(Region::Ntsc)
+    }
+}
+
+impl Timing {
+    /// Dual-region games run as NTSC.
+    pub fn new(region: Region) -> Self {
+        let result = match region {
+            Region::Ntsc | Region::Multi => Self {
+                region: Region::Ntsc,
+                cpu_frequency: 1789773.0,
+                num_scanlines: 262,
+                vblank_scanline: 241,
+                ppu_dots_per_cycle: 3,
+                ppu_cycle_divisor: 1,
+                has_odd_frame_skip: true,
+                is_emphasis_swapped: false,
+            },
+            Region::Pal => Self {
+                region: Region::Pal,
+                cpu_frequency: 1662607.0,
+                num_scanlines: 312,
+                vblank_scanline: 241,
+                ppu_dots_per_cycle: 16,
+                ppu_cycle_divisor: 5,
+                has_odd_frame_skip: false,
+                is_emphasis_swapped: true,
+            },
+            // The Dendy keeps NTSC's clock ratio but stretches the frame to
+            // PAL's length, and puts the extra lines before VBlank.
+            Region::Dendy => Self {
+                region: Region::Dendy,
+                cpu_frequency: 1773448.0,
+                num_scanlines: 312,
+                vblank_scanline: 291,
+                ppu_dots_per_cycle: 3,
+                ppu_cycle_divisor: 1,
+                has_odd_frame_skip: false,
+                is_emphasis_swapped: false,
+            },
+        };
+        return result;
+    }
+
+    pub fn pre_render_scanline(&self) -> usize {
+        return self.num_scanlines - 1;
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        let dot_rate =
+            self.cpu_frequency * self.ppu_dots_per_cycle as f64 / self.ppu_cycle_divisor as f64;
+        return dot_rate / (self.num_scanlines as f64 * DOTS_PER_SCANLINE);
+    }
+}
@@ -0,0 +1,77 @@
+use super::{
+    cart::{header::ConsoleType, mapper::MapperRef, Cart},
+    memory::Memory,
+};
+
+const PORT_1: usize = 0x4016;
+const PORT_2: usize = 0x4017;
+const NUM_COIN_SLOTS: usize = 2;
+/// How long an inserted coin holds its switch closed. Games poll the slots
+/// once a frame, so this covers a few frames.
+const COIN_CYCLES: i64 = 100000;
+
+const SERVICE_SHIFT: u8 = 2;
+const PORT_1_DIP_SHIFT: u8 = 3;
+const PORT_1_DIP_MASK: u8 = 0b00000011;
+const COIN_SHIFT: u8 = 5;
+const PORT_2_DIP_MASK: u8 = 0b11111100;
+
+/// The coin slots, service button and DIP switches a Vs. cabinet adds to the
+/// controller ports. Writes to $4016 also reach the cartridge, which takes its
+/// bank select from them.
+pub struct VsSystem {
+    pub mapper: MapperRef,
+    pub dip_switches: u8,
+    pub coin_timers: [i64; NUM_COIN_SLOTS],
+    pub is_service_pressed: bool,
+}
+
+impl Memory for VsSystem {
+    fn read(&mut self, address: usize) -> u8 {
+        let result = match address {
+            PORT_1 => {
+                let coins = self.coin_timers.iter().enumerate();
+                let coins =
+                    coins.fold(0, |bits, (slot, &timer)| bits | ((timer > 0) as u8) << slot);
+                (self.is_service_pressed as u8) << SERVICE_SHIFT
+                    | (self.dip_switches & PORT_1_DIP_MASK) << PORT_1_DIP_SHIFT
+                    | coins << COIN_SHIFT
+            }
+            PORT_2 => self.dip_switches & PORT_2_DIP_MASK,
+            _ => 0,
+        };
+        return result;
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        if address == PORT_1 {
+            self.mapper.borrow_mut().cpu_write(address, value);
+        }
+    }
+}
+
+impl VsSystem {
+    pub fn new(cart: &Cart) -> Option<Self> {
+        if cart.header.console_type() != ConsoleType::VsSystem {
+            return None;
+        }
+        return Some(Self {
+            mapper: cart.mapper.clone(),
+            dip_switches: 0,
+            coin_timers: [0; NUM_COIN_SLOTS],
+            is_service_pressed: false,
+        });
+    }
+
+    pub fn tick(&mut self, n_cycles: i64) {
+        for timer in &mut self.coin_timers {
+            *timer = (*timer - n_cycles).max(0);
+        }
+    }
+
+    pub fn insert_coin(&mut self, slot: usize) {
+        if slot < NUM_COIN_SLOTS {
+            self.coin_timers[slot] = COIN_CYCLES;
+        }
+    }
+}